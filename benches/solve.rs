@@ -0,0 +1,75 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use effort::{
+    create_challenge, solve_challenge, solve_challenge_blocking, verify_solution, Difficulty,
+    HashAlgorithm, NoProgress, PowHasher, Puzzle, SolveOptions,
+};
+use std::hint::black_box;
+
+// A single attempt at a fragment, hashing the fragment, a nonce and a context
+fn attempt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("attempt");
+    group.throughput(Throughput::Elements(1));
+
+    let data = [&[0x5a; 16][..], &42u128.to_le_bytes(), b"/login"].concat();
+    let mut out = [0; 64];
+    let argon2id = HashAlgorithm::Argon2id {
+        memory_kib: 1024,
+        iterations: 1,
+    };
+    for algorithm in HashAlgorithm::ALL.into_iter().chain([argon2id]) {
+        group.bench_function(format!("{algorithm:?}"), |b| {
+            b.iter(|| algorithm.hash(black_box(&data), &mut out))
+        });
+    }
+    group.finish();
+}
+
+fn solve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("solve_challenge");
+    group.sample_size(10);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    for bits in [8, 12, 16] {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(bits), 8);
+        group.throughput(Throughput::Elements(challenge.expected_attempts() as u64));
+
+        group.bench_with_input(BenchmarkId::new("async", bits), &challenge, |b, c| {
+            b.to_async(&runtime)
+                .iter(|| solve_challenge(c, &NoProgress))
+        });
+        group.bench_with_input(BenchmarkId::new("blocking", bits), &challenge, |b, c| {
+            b.iter(|| solve_challenge_blocking(c, &NoProgress, &SolveOptions::default()))
+        });
+    }
+    group.finish();
+}
+
+// What a server pays for every submitted solution
+fn verify(c: &mut Criterion) {
+    let mut group = c.benchmark_group("verify_solution");
+
+    let puzzles = [
+        Puzzle::HashTarget,
+        Puzzle::CuckooCycle {
+            edge_bits: 12,
+            cycle_len: 8,
+        },
+        Puzzle::TimeLock { squarings: 5000 },
+    ];
+    for puzzle in puzzles {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(2), 8)
+            .with_puzzle(puzzle)
+            .unwrap();
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+        assert!(verify_solution(&challenge, &solution));
+
+        group.bench_function(format!("{puzzle:?}"), |b| {
+            b.iter(|| verify_solution(black_box(&challenge), black_box(&solution)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, attempt, solve, verify);
+criterion_main!(benches);
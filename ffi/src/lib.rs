@@ -0,0 +1,4 @@
+//! Static and dynamic C library for effort. The functions are defined in
+//! `effort::ffi` and declared in `include/effort.h`.
+
+pub use effort::ffi::*;
@@ -0,0 +1,66 @@
+#ifndef EFFORT_H
+#define EFFORT_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct PowHash {
+  uint32_t len;
+  const uint8_t *data;
+} PowHash;
+
+/**
+ * Creates a challenge and returns it serialized. `context` may be null when
+ * `context_len` is 0.
+ *
+ * # Safety
+ *
+ * `context` must point to `context_len` readable bytes.
+ */
+struct PowHash effort_challenge_create(uint32_t difficulty,
+                                       uint32_t num_fragments,
+                                       const uint8_t *context,
+                                       uint32_t context_len);
+
+/**
+ * Solves a serialized challenge on `threads` threads, or one per CPU if
+ * `threads` is 0, and returns the serialized solution. Blocks until done.
+ *
+ * # Safety
+ *
+ * `challenge` must describe `len` readable bytes.
+ */
+struct PowHash effort_challenge_solve(struct PowHash challenge, uint32_t threads);
+
+/**
+ * Returns 1 if `solution` solves `challenge` in `context`, with one proof per
+ * fragment in the challenge's order, 0 if it doesn't and -1 if either buffer
+ * is malformed. `context` comes from the request presenting the solution, not
+ * from the challenge it sent back, and may be null when `context_len` is 0.
+ *
+ * # Safety
+ *
+ * Both buffers must describe `len` readable bytes, and `context` must point
+ * to `context_len` readable bytes.
+ */
+int32_t effort_solution_verify(struct PowHash challenge,
+                               struct PowHash solution,
+                               const uint8_t *context,
+                               uint32_t context_len);
+
+/**
+ * Releases a buffer returned by this library. Freeing a null `PowHash` does
+ * nothing.
+ *
+ * # Safety
+ *
+ * `hash` must have been returned by this library and not freed before.
+ */
+void effort_free(struct PowHash hash);
+
+#endif  /* EFFORT_H */
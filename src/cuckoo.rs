@@ -0,0 +1,268 @@
+//! Cuckoo Cycle: every nonce picks a pseudo-random bipartite graph with
+//! 2^edge_bits edges, and a proof is the indices of edges forming a cycle of a
+//! given length. Finding one means building most of the graph in memory, while
+//! checking one takes a SipHash per edge of the cycle.
+
+#[cfg(any(feature = "solve", test))]
+use alloc::collections::BTreeSet;
+#[cfg(any(feature = "solve", test))]
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Paths longer than this are given up on; they are vanishingly rare
+#[cfg(any(feature = "solve", test))]
+const MAX_PATH: usize = 8192;
+
+/// Whether the graph parameters make sense at all. Cycles alternate between
+/// both sides of the graph, so their length is even, and can't have more
+/// edges than the graph.
+pub(crate) fn valid(edge_bits: u8, cycle_len: u8) -> bool {
+    (1..=31).contains(&edge_bits)
+        && cycle_len >= 4
+        && cycle_len.is_multiple_of(2)
+        && cycle_len as u32 <= 1 << edge_bits
+}
+
+/// Looks for a cycle of `cycle_len` edges in the graph keyed by `keys`,
+/// returning its edge indices in ascending order
+#[cfg(any(feature = "solve", test))]
+pub(crate) fn find_cycle(keys: &[u64; 4], edge_bits: u8, cycle_len: u8) -> Option<Vec<u32>> {
+    if !valid(edge_bits, cycle_len) {
+        return None;
+    }
+
+    // Every node points towards the root of its tree, 0 standing for none
+    let mut cuckoo = vec![0u32; 2 << edge_bits];
+    let (mut us, mut vs) = (Vec::new(), Vec::new());
+
+    for edge in 0..1u32 << edge_bits {
+        let (u0, v0) = (
+            node(keys, edge, 0, edge_bits),
+            node(keys, edge, 1, edge_bits),
+        );
+        if u0 == 0 {
+            continue;
+        }
+
+        us.clear();
+        us.push(u0);
+        vs.clear();
+        vs.push(v0);
+        if !path(&cuckoo, &mut us) || !path(&cuckoo, &mut vs) {
+            continue;
+        }
+
+        let (mut nu, mut nv) = (us.len() - 1, vs.len() - 1);
+        if us[nu] == vs[nv] {
+            // Same tree, so this edge closes a cycle. Find where the paths join.
+            let min = nu.min(nv);
+            nu -= min;
+            nv -= min;
+            while us[nu] != vs[nv] {
+                nu += 1;
+                nv += 1;
+            }
+
+            if nu + nv + 1 == cycle_len as usize {
+                return recover(keys, edge_bits, &us[..=nu], &vs[..=nv]);
+            }
+            continue;
+        }
+
+        // Reverse the shorter path so the new edge can join both trees
+        if nu < nv {
+            for i in (0..nu).rev() {
+                cuckoo[us[i + 1] as usize] = us[i];
+            }
+            cuckoo[u0 as usize] = v0;
+        } else {
+            for i in (0..nv).rev() {
+                cuckoo[vs[i + 1] as usize] = vs[i];
+            }
+            cuckoo[v0 as usize] = u0;
+        }
+    }
+
+    None
+}
+
+/// Checks that `edges` are ascending and form a single cycle of `cycle_len`
+/// edges in the graph keyed by `keys`
+pub(crate) fn verify_cycle(keys: &[u64; 4], edge_bits: u8, cycle_len: u8, edges: &[u32]) -> bool {
+    let len = cycle_len as usize;
+    if !valid(edge_bits, cycle_len) || edges.len() != len {
+        return false;
+    }
+
+    let mut uvs = Vec::with_capacity(2 * len);
+    let (mut xor_u, mut xor_v) = (0, 0);
+    for (i, &edge) in edges.iter().enumerate() {
+        if edge >> edge_bits != 0 || (i > 0 && edge <= edges[i - 1]) {
+            return false;
+        }
+
+        let (u, v) = (
+            node(keys, edge, 0, edge_bits),
+            node(keys, edge, 1, edge_bits),
+        );
+        uvs.extend([u, v]);
+        xor_u ^= u;
+        xor_v ^= v;
+    }
+
+    // Every node of a cycle is an endpoint of exactly two of its edges
+    if xor_u != 0 || xor_v != 0 {
+        return false;
+    }
+
+    // Walk the cycle, alternating sides, and make sure it visits every edge
+    let (mut i, mut steps) = (0, 0);
+    loop {
+        let mut j = i;
+        let mut k = i;
+        loop {
+            k = (k + 2) % (2 * len);
+            if k == i {
+                break;
+            }
+            if uvs[k] == uvs[i] {
+                // A node shared by more than two edges
+                if j != i {
+                    return false;
+                }
+                j = k;
+            }
+        }
+
+        // A dead end
+        if j == i {
+            return false;
+        }
+
+        i = j ^ 1;
+        steps += 1;
+        if i == 0 {
+            break;
+        }
+    }
+
+    steps == len
+}
+
+/// SipHash keys from the first 32 bytes of `seed`
+pub(crate) fn keys(seed: &[u8]) -> [u64; 4] {
+    let mut keys = [0; 4];
+    for (key, bytes) in keys.iter_mut().zip(seed.chunks_exact(8)) {
+        *key = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+    keys
+}
+
+// Follows the tree from the last node in `nodes` up to its root, false if the
+// path gets too long
+#[cfg(any(feature = "solve", test))]
+fn path(cuckoo: &[u32], nodes: &mut Vec<u32>) -> bool {
+    let mut u = cuckoo[nodes[nodes.len() - 1] as usize];
+    while u != 0 {
+        if nodes.len() >= MAX_PATH {
+            return false;
+        }
+        nodes.push(u);
+        u = cuckoo[u as usize];
+    }
+    true
+}
+
+// Turns the cycle's nodes back into edge indices by regenerating the graph
+#[cfg(any(feature = "solve", test))]
+fn recover(keys: &[u64; 4], edge_bits: u8, us: &[u32], vs: &[u32]) -> Option<Vec<u32>> {
+    let ordered = |a: u32, b: u32| if a.is_multiple_of(2) { (a, b) } else { (b, a) };
+    let mut wanted: BTreeSet<_> = us
+        .windows(2)
+        .chain(vs.windows(2))
+        .map(|w| ordered(w[0], w[1]))
+        .collect();
+    wanted.insert((us[0], vs[0]));
+    let len = wanted.len();
+
+    let mut edges = Vec::with_capacity(len);
+    for edge in 0..1u32 << edge_bits {
+        let pair = (
+            node(keys, edge, 0, edge_bits),
+            node(keys, edge, 1, edge_bits),
+        );
+        if wanted.remove(&pair) {
+            edges.push(edge);
+        }
+    }
+
+    (edges.len() == len).then_some(edges)
+}
+
+// Endpoint of `edge` on `side`. Nodes on side 0 are even, those on side 1 odd.
+fn node(keys: &[u64; 4], edge: u32, side: u32, edge_bits: u8) -> u32 {
+    let mask = (1u64 << edge_bits) - 1;
+    let hash = siphash24(keys, 2 * edge as u64 + side as u64);
+    ((hash & mask) << 1) as u32 | side
+}
+
+// SipHash-2-4 over a single word, with the whole state taken from `keys`
+fn siphash24(keys: &[u64; 4], nonce: u64) -> u64 {
+    let mut v = *keys;
+    v[3] ^= nonce;
+    sip_round(&mut v);
+    sip_round(&mut v);
+    v[0] ^= nonce;
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+fn sip_round(v: &mut [u64; 4]) {
+    v[0] = v[0].wrapping_add(v[1]);
+    v[2] = v[2].wrapping_add(v[3]);
+    v[1] = v[1].rotate_left(13);
+    v[3] = v[3].rotate_left(16);
+    v[1] ^= v[0];
+    v[3] ^= v[2];
+    v[0] = v[0].rotate_left(32);
+    v[2] = v[2].wrapping_add(v[1]);
+    v[0] = v[0].wrapping_add(v[3]);
+    v[1] = v[1].rotate_left(17);
+    v[3] = v[3].rotate_left(21);
+    v[1] ^= v[2];
+    v[3] ^= v[0];
+    v[2] = v[2].rotate_left(32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_verifies_cycles() {
+        let (edge_bits, cycle_len) = (12, 8);
+        let (keys, edges) = (0..)
+            .map(|n: u64| [n, 1, 2, 3])
+            .find_map(|keys| Some((keys, find_cycle(&keys, edge_bits, cycle_len)?)))
+            .unwrap();
+
+        assert_eq!(edges.len(), 8);
+        assert!(verify_cycle(&keys, edge_bits, cycle_len, &edges));
+
+        // Any other edge breaks the cycle
+        let spare = (0..1 << edge_bits).rev().find(|e| !edges.contains(e));
+        let mut broken = edges.clone();
+        broken[7] = spare.unwrap();
+        assert!(!verify_cycle(&keys, edge_bits, cycle_len, &broken));
+        assert!(!verify_cycle(&keys, edge_bits, cycle_len, &edges[1..]));
+        assert!(!verify_cycle(&[9, 9, 9, 9], edge_bits, cycle_len, &edges));
+
+        let mut unordered = edges;
+        unordered.swap(0, 1);
+        assert!(!verify_cycle(&keys, edge_bits, cycle_len, &unordered));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::WireError;
+use core::time::Duration;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// How hard it is to find a nonce for a single fragment.
+///
+/// Every variant boils down to the probability that one hash attempt succeeds,
+/// which is what [`expected_attempts`](Self::expected_attempts) reports.
+///
+/// Human-readable formats also take a bare integer, as [`Legacy`](Self::Legacy),
+/// which is how challenges stored their difficulty before there were other
+/// models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Difficulty {
+    /// The original model: the first four bytes of the hash, read as a
+    /// big-endian `u32`, must be below `u32::MAX - difficulty`
+    Legacy(u32),
+    /// The hash must start with this many zero bits
+    LeadingZeroBits(u16),
+    /// The hash, read as a big-endian 512-bit integer, must be below this
+    Target(#[serde(with = "target_bytes")] [u8; 64]),
+}
+
+impl Difficulty {
+    /// Builds the target equivalent to `LeadingZeroBits(bits)`, i.e. 2^(512 - bits)
+    pub fn target_for_bits(bits: u16) -> [u8; 64] {
+        let mut target = [0; 64];
+        match bits {
+            // 2^512 itself doesn't fit, but only an all-ones hash is excluded
+            0 => target = [0xff; 64],
+            1..=512 => {
+                let bit = 512 - bits as usize;
+                target[63 - bit / 8] = 1 << (bit % 8);
+            }
+            _ => {}
+        }
+        target
+    }
+
+    pub fn meets(&self, hash: &[u8]) -> bool {
+        match self {
+            Difficulty::Legacy(difficulty) => {
+                let first_four_bytes = [hash[0], hash[1], hash[2], hash[3]];
+                u32::from_be_bytes(first_four_bytes) < (u32::MAX - difficulty)
+            }
+            Difficulty::LeadingZeroBits(bits) => leading_zero_bits(hash) >= *bits as u32,
+            Difficulty::Target(target) => hash[..] < target[..hash.len().min(64)],
+        }
+    }
+
+    /// Chance that a single hash attempt with a 64-byte digest meets this
+    /// difficulty
+    pub fn success_probability(&self) -> f64 {
+        self.success_probability_for(64)
+    }
+
+    /// Chance that a single hash attempt with a `digest_len`-byte digest meets
+    /// this difficulty. Shorter digests can't meet more zero bits than they
+    /// have, nor targets below 2^(8 * `digest_len`).
+    pub fn success_probability_for(&self, digest_len: usize) -> f64 {
+        let len = digest_len.min(64);
+        match self {
+            Difficulty::Legacy(difficulty) => (u32::MAX - difficulty) as f64 * exp2(-32),
+            Difficulty::LeadingZeroBits(bits) if *bits as usize > 8 * len => 0.0,
+            Difficulty::LeadingZeroBits(bits) => exp2(-(*bits as i32)),
+            // `meets` compares the digest with as many leading bytes of the
+            // target
+            Difficulty::Target(target) => {
+                target[..len]
+                    .iter()
+                    .fold(0.0, |acc, &b| acc * 256.0 + b as f64)
+                    * exp2(-8 * len as i32)
+            }
+        }
+    }
+
+    /// Mean number of 64-byte hashes needed to solve one fragment, which is
+    /// infinite when the difficulty can't be met at all. See
+    /// [`Challenge::expected_attempts`](crate::Challenge::expected_attempts)
+    /// for other digest lengths.
+    pub fn expected_attempts(&self) -> f64 {
+        1.0 / self.success_probability()
+    }
+
+    /// Mean time to solve one fragment at `hashes_per_second`
+    pub fn estimated_solve_time(&self, hashes_per_second: f64) -> Duration {
+        Duration::try_from_secs_f64(self.expected_attempts() / hashes_per_second)
+            .unwrap_or(Duration::MAX)
+    }
+
+    /// Picks a difficulty and fragment count for challenges that take about
+    /// `target` to solve at `hashes_per_second`. Pass the rate `calibrate`
+    /// measured on the slowest device worth supporting. The work is split over
+    /// up to 32 fragments, since a single fragment's solve time varies wildly.
+    pub fn for_solve_time(hashes_per_second: f64, target: Duration) -> (Difficulty, usize) {
+        const MAX_FRAGMENTS: usize = 32;
+        let attempts = hashes_per_second * target.as_secs_f64();
+
+        // The fewest zero bits that need no more than the most fragments, then
+        // as many fragments as make up the rest
+        let mut bits = 0;
+        while bits < 512 && exp2(bits) * (MAX_FRAGMENTS as f64) < attempts {
+            bits += 1;
+        }
+        let fragments = ((attempts / exp2(bits) + 0.5) as usize).clamp(1, MAX_FRAGMENTS);
+        (Difficulty::LeadingZeroBits(bits as u16), fragments)
+    }
+
+    // Canonical encoding, used wherever a difficulty gets authenticated or sent
+    pub(crate) fn to_bytes(self) -> alloc::vec::Vec<u8> {
+        match self {
+            Difficulty::Legacy(difficulty) => [&[0][..], &difficulty.to_le_bytes()].concat(),
+            Difficulty::LeadingZeroBits(bits) => [&[1][..], &bits.to_le_bytes()].concat(),
+            Difficulty::Target(target) => [&[2][..], &target[..]].concat(),
+        }
+    }
+
+    // Reverses `to_bytes`, returning whatever follows the difficulty
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), WireError> {
+        let (&kind, rest) = bytes.split_first().ok_or(WireError::Truncated)?;
+        match kind {
+            0 => {
+                let (value, rest) = rest.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+                Ok((Difficulty::Legacy(u32::from_le_bytes(*value)), rest))
+            }
+            1 => {
+                let (value, rest) = rest.split_first_chunk::<2>().ok_or(WireError::Truncated)?;
+                Ok((
+                    Difficulty::LeadingZeroBits(u16::from_le_bytes(*value)),
+                    rest,
+                ))
+            }
+            2 => {
+                let (value, rest) = rest.split_first_chunk::<64>().ok_or(WireError::Truncated)?;
+                Ok((Difficulty::Target(*value), rest))
+            }
+            _ => Err(WireError::UnknownDifficulty(kind)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Difficulty {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Telling an integer from an enum needs a self-describing format, and
+        // binary ones never had the bare integer anyway
+        if deserializer.is_human_readable() {
+            Ok(match Compatible::deserialize(deserializer)? {
+                Compatible::Legacy(difficulty) => Difficulty::Legacy(difficulty),
+                Compatible::Tagged(tagged) => tagged.into(),
+            })
+        } else {
+            Tagged::deserialize(deserializer).map(Into::into)
+        }
+    }
+}
+
+// What `Difficulty` would derive, to deserialize it into
+#[derive(Deserialize)]
+#[serde(rename = "Difficulty")]
+enum Tagged {
+    Legacy(u32),
+    LeadingZeroBits(u16),
+    Target(#[serde(with = "target_bytes")] [u8; 64]),
+}
+
+impl From<Tagged> for Difficulty {
+    fn from(tagged: Tagged) -> Self {
+        match tagged {
+            Tagged::Legacy(difficulty) => Difficulty::Legacy(difficulty),
+            Tagged::LeadingZeroBits(bits) => Difficulty::LeadingZeroBits(bits),
+            Tagged::Target(target) => Difficulty::Target(target),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Compatible {
+    Legacy(u32),
+    Tagged(Tagged),
+}
+
+impl From<u32> for Difficulty {
+    fn from(difficulty: u32) -> Self {
+        Difficulty::Legacy(difficulty)
+    }
+}
+
+// 2^n for -1022 <= n <= 1023, built directly since `powi` needs `std`
+fn exp2(n: i32) -> f64 {
+    f64::from_bits(((1023 + n) as u64) << 52)
+}
+
+fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for b in hash {
+        bits += b.leading_zeros();
+        if *b != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+// serde only handles arrays of up to 32 elements
+mod target_bytes {
+    use alloc::vec::Vec;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(target: &[u8; 64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(target)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 64], D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        bytes
+            .try_into()
+            .map_err(|b: Vec<u8>| D::Error::invalid_length(b.len(), &"64 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_attempts() {
+        assert_eq!(Difficulty::LeadingZeroBits(0).expected_attempts(), 1.0);
+        assert_eq!(
+            Difficulty::LeadingZeroBits(20).expected_attempts(),
+            1048576.0
+        );
+        assert_eq!(
+            Difficulty::Target(Difficulty::target_for_bits(20)).expected_attempts(),
+            1048576.0
+        );
+        assert_eq!(
+            Difficulty::Legacy(u32::MAX - (1 << 12)).expected_attempts(),
+            1048576.0
+        );
+        assert_eq!(
+            Difficulty::Legacy(u32::MAX).expected_attempts(),
+            f64::INFINITY
+        );
+
+        assert_eq!(
+            Difficulty::LeadingZeroBits(20).estimated_solve_time(1048576.0 / 2.0),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            Difficulty::LeadingZeroBits(600).estimated_solve_time(1e9),
+            Duration::MAX
+        );
+    }
+
+    #[test]
+    fn for_solve_time() {
+        let (difficulty, fragments) =
+            Difficulty::for_solve_time(1_000_000.0, Duration::from_secs(10));
+        assert_eq!(
+            (difficulty, fragments),
+            (Difficulty::LeadingZeroBits(19), 19)
+        );
+        assert_eq!(fragments as f64 * difficulty.expected_attempts(), 9961472.0);
+
+        assert_eq!(
+            Difficulty::for_solve_time(10.0, Duration::from_secs(1)),
+            (Difficulty::LeadingZeroBits(0), 10)
+        );
+        assert_eq!(
+            Difficulty::for_solve_time(0.0, Duration::from_secs(1)),
+            (Difficulty::LeadingZeroBits(0), 1)
+        );
+    }
+
+    #[test]
+    fn deserializes_legacy_integers() {
+        let old: Difficulty = serde_json::from_str("1000").unwrap();
+        assert_eq!(old, Difficulty::Legacy(1000));
+        assert!(serde_json::from_str::<Difficulty>("-1").is_err());
+        assert!(serde_json::from_str::<Difficulty>("4294967296").is_err());
+
+        for difficulty in [
+            Difficulty::Legacy(7),
+            Difficulty::LeadingZeroBits(20),
+            Difficulty::Target([0x0f; 64]),
+        ] {
+            let json = serde_json::to_string(&difficulty).unwrap();
+            assert_eq!(
+                serde_json::from_str::<Difficulty>(&json).unwrap(),
+                difficulty
+            );
+
+            let bytes = bincode::serialize(&difficulty).unwrap();
+            assert_eq!(
+                bincode::deserialize::<Difficulty>(&bytes).unwrap(),
+                difficulty
+            );
+        }
+    }
+
+    #[test]
+    fn short_digests() {
+        for len in [32, 64] {
+            assert_eq!(
+                Difficulty::LeadingZeroBits(200).success_probability_for(len),
+                exp2(-200)
+            );
+            assert_eq!(
+                Difficulty::Target(Difficulty::target_for_bits(20)).success_probability_for(len),
+                exp2(-20)
+            );
+        }
+
+        // Only a 64-byte digest can have this many zero bits, or be this small
+        let tiny = Difficulty::Target(Difficulty::target_for_bits(300));
+        for difficulty in [Difficulty::LeadingZeroBits(300), tiny] {
+            assert_eq!(difficulty.success_probability_for(32), 0.0);
+            assert_eq!(difficulty.success_probability(), exp2(-300));
+        }
+    }
+
+    #[test]
+    fn leading_zero_bits_match_target() {
+        let hash = [[0, 0, 0b0001_0000].as_slice(), &[0xff; 61]].concat();
+
+        assert!(Difficulty::LeadingZeroBits(19).meets(&hash));
+        assert!(!Difficulty::LeadingZeroBits(20).meets(&hash));
+        assert!(Difficulty::Target(Difficulty::target_for_bits(19)).meets(&hash));
+        assert!(!Difficulty::Target(Difficulty::target_for_bits(20)).meets(&hash));
+    }
+}
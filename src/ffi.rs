@@ -0,0 +1,191 @@
+//! C interface, declared in `include/effort.h` and built into a static and
+//! dynamic library by the `effort-ffi` crate. Challenges and solutions cross the
+//! boundary in [`PowHash`] buffers, in the format of [`Challenge::to_bytes`]
+//! and [`Solution::to_bytes`].
+//!
+//! Every `PowHash` returned by a function here is owned by the caller and must
+//! be released with [`effort_free`]. A `PowHash` with a null `data` pointer
+//! signals failure. Buffers passed in are only borrowed for the duration of
+//! the call.
+
+use crate::{
+    create_challenge_with_context, solve_challenge_blocking, verify_solution_strict_with_context,
+    Challenge, NoProgress, PowHash, Solution, SolveOptions, SolverBackend,
+};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::{ptr, slice};
+
+impl PowHash {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        // Too long for C to be told its length
+        let Ok(len) = u32::try_from(bytes.len()) else {
+            return PowHash::null();
+        };
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *const u8;
+        PowHash { len, data }
+    }
+
+    fn null() -> Self {
+        PowHash {
+            len: 0,
+            data: ptr::null(),
+        }
+    }
+
+    unsafe fn as_slice<'a>(&self) -> &'a [u8] {
+        if self.data.is_null() {
+            &[]
+        } else {
+            slice::from_raw_parts(self.data, self.len as usize)
+        }
+    }
+}
+
+/// Creates a challenge and returns it serialized. `context` may be null when
+/// `context_len` is 0.
+///
+/// # Safety
+///
+/// `context` must point to `context_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn effort_challenge_create(
+    difficulty: u32,
+    num_fragments: u32,
+    context: *const u8,
+    context_len: u32,
+) -> PowHash {
+    let context = PowHash {
+        len: context_len,
+        data: context,
+    };
+    let challenge =
+        create_challenge_with_context(difficulty, num_fragments as usize, context.as_slice());
+
+    PowHash::from_vec(challenge.to_bytes())
+}
+
+/// Solves a serialized challenge on `threads` threads, or one per CPU if
+/// `threads` is 0, and returns the serialized solution. Blocks until done.
+///
+/// # Safety
+///
+/// `challenge` must describe `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn effort_challenge_solve(challenge: PowHash, threads: u32) -> PowHash {
+    let Ok(challenge) = Challenge::from_bytes(challenge.as_slice()) else {
+        return PowHash::null();
+    };
+
+    let mut options = SolveOptions::default();
+    if threads > 0 {
+        options.backend = SolverBackend::Threads(threads as usize);
+    }
+
+    match solve_challenge_blocking(&challenge, &NoProgress, &options) {
+        Ok(solution) => PowHash::from_vec(solution.to_bytes()),
+        Err(_) => PowHash::null(),
+    }
+}
+
+/// Returns 1 if `solution` solves `challenge` in `context`, with one proof per
+/// fragment in the challenge's order, 0 if it doesn't and -1 if either buffer
+/// is malformed. `context` comes from the request presenting the solution, not
+/// from the challenge it sent back, and may be null when `context_len` is 0.
+///
+/// # Safety
+///
+/// Both buffers must describe `len` readable bytes, and `context` must point
+/// to `context_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn effort_solution_verify(
+    challenge: PowHash,
+    solution: PowHash,
+    context: *const u8,
+    context_len: u32,
+) -> i32 {
+    let (Ok(challenge), Ok(solution)) = (
+        Challenge::from_bytes(challenge.as_slice()),
+        Solution::from_bytes(solution.as_slice()),
+    ) else {
+        return -1;
+    };
+    let context = PowHash {
+        len: context_len,
+        data: context,
+    };
+
+    verify_solution_strict_with_context(&challenge, &solution, context.as_slice()).is_ok() as i32
+}
+
+/// Releases a buffer returned by this library. Freeing a null `PowHash` does
+/// nothing.
+///
+/// # Safety
+///
+/// `hash` must have been returned by this library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn effort_free(hash: PowHash) {
+    if !hash.data.is_null() {
+        let data = ptr::slice_from_raw_parts_mut(hash.data as *mut u8, hash.len as usize);
+        drop(Box::from_raw(data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A second handle to the same buffer, which stays owned by `hash`
+    fn borrow(hash: &PowHash) -> PowHash {
+        PowHash {
+            len: hash.len,
+            data: hash.data,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        unsafe {
+            let context = b"session 42";
+            let challenge = effort_challenge_create(u32::MAX - (1 << 24), 2, context.as_ptr(), 10);
+            let solution = effort_challenge_solve(borrow(&challenge), 2);
+
+            assert!(!solution.data.is_null());
+            let verify = |solution, context: &[u8]| {
+                effort_solution_verify(
+                    borrow(&challenge),
+                    solution,
+                    context.as_ptr(),
+                    context.len() as u32,
+                )
+            };
+            assert_eq!(verify(borrow(&solution), context), 1);
+            // Relayed to another client, or without the request's context
+            assert_eq!(verify(borrow(&solution), b"session 43"), 0);
+            assert_eq!(
+                effort_solution_verify(borrow(&challenge), borrow(&solution), ptr::null(), 0),
+                0
+            );
+
+            let mut tampered = solution.as_slice().to_vec();
+            // Flip a bit of the first fragment
+            tampered[7] ^= 1;
+            let tampered = PowHash {
+                len: tampered.len() as u32,
+                data: tampered.as_ptr(),
+            };
+            assert_eq!(verify(tampered, context), 0);
+
+            let truncated = PowHash {
+                len: solution.len - 1,
+                data: solution.data,
+            };
+            assert_eq!(verify(truncated, context), -1);
+
+            effort_free(challenge);
+            effort_free(solution);
+            effort_free(PowHash::null());
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::WireError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::{Blake2b512, Blake2s256};
+use core::marker::PhantomData;
+use serde::{Deserialize, Serialize};
+use sha2::digest::Digest;
+use sha2::Sha256;
+use sha3::{Keccak256, Sha3_256};
+
+/// A hash function that proofs of work can be computed with. The built-in
+/// ones are [`DigestHasher`] and [`Argon2id`], which [`HashAlgorithm`] picks
+/// between for challenges.
+pub trait PowHasher {
+    /// Writes the digest of `data` to the start of `out` and returns its length
+    fn hash(&self, data: &[u8], out: &mut [u8; 64]) -> usize;
+}
+
+/// Any fixed-size RustCrypto digest of up to 64 bytes, such as
+/// `DigestHasher::<Sha256>::new()`
+pub struct DigestHasher<D>(PhantomData<D>);
+
+impl<D> DigestHasher<D> {
+    pub const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<D> Default for DigestHasher<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<D: Digest> PowHasher for DigestHasher<D> {
+    fn hash(&self, data: &[u8], out: &mut [u8; 64]) -> usize {
+        let hash = D::digest(data);
+        out[..hash.len()].copy_from_slice(&hash);
+        hash.len()
+    }
+}
+
+/// Memory-hard Argon2id with a 32-byte output, filling `memory_kib` KiB of RAM
+/// `iterations` times over for every hash
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2id {
+    pub memory_kib: u32,
+    pub iterations: u32,
+}
+
+impl PowHasher for Argon2id {
+    fn hash(&self, data: &[u8], out: &mut [u8; 64]) -> usize {
+        // Out of range costs are clamped rather than rejected, so that every
+        // challenge still has exactly one meaning
+        let params = Params::new(
+            self.memory_kib.max(Params::MIN_M_COST),
+            self.iterations.max(Params::MIN_T_COST),
+            1,
+            Some(32),
+        )
+        .unwrap();
+
+        // Fragments are random, so a fixed salt doesn't allow any precomputation
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(data, b"effort-argon2id", &mut out[..32])
+            .unwrap();
+        32
+    }
+}
+
+/// The hash functions a [`Challenge`](crate::Challenge) can name, each backed
+/// by one of the [`PowHasher`]s above
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake2b512,
+    Blake2s256,
+    Sha256,
+    Sha3_256,
+    Keccak256,
+    /// Memory-hard Argon2id, so that every attempt has to fill `memory_kib`
+    /// KiB of RAM `iterations` times over. This narrows the gap between GPUs or
+    /// ASICs and phones, but makes verification as expensive as one attempt.
+    Argon2id {
+        memory_kib: u32,
+        iterations: u32,
+    },
+}
+
+impl HashAlgorithm {
+    /// Every fixed-cost hash function
+    pub const ALL: [HashAlgorithm; 5] = [
+        HashAlgorithm::Blake2b512,
+        HashAlgorithm::Blake2s256,
+        HashAlgorithm::Sha256,
+        HashAlgorithm::Sha3_256,
+        HashAlgorithm::Keccak256,
+    ];
+
+    /// Length in bytes of the digests [`hash`](PowHasher::hash) writes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Blake2b512 => 64,
+            _ => 32,
+        }
+    }
+
+    /// Stable numeric identifier, not including any parameters
+    pub fn id(&self) -> u8 {
+        match self {
+            HashAlgorithm::Blake2b512 => 0,
+            HashAlgorithm::Blake2s256 => 1,
+            HashAlgorithm::Sha256 => 2,
+            HashAlgorithm::Sha3_256 => 3,
+            HashAlgorithm::Keccak256 => 4,
+            HashAlgorithm::Argon2id { .. } => 5,
+        }
+    }
+
+    // Canonical encoding: the ID, followed by any parameters
+    pub(crate) fn to_bytes(self) -> alloc::vec::Vec<u8> {
+        match self {
+            HashAlgorithm::Argon2id {
+                memory_kib,
+                iterations,
+            } => [
+                &[self.id()][..],
+                &memory_kib.to_le_bytes(),
+                &iterations.to_le_bytes(),
+            ]
+            .concat(),
+            _ => alloc::vec![self.id()],
+        }
+    }
+
+    // Reverses `to_bytes`, returning whatever follows the algorithm
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), WireError> {
+        let (&id, rest) = bytes.split_first().ok_or(WireError::Truncated)?;
+        if id == 5 {
+            let (memory_kib, rest) = rest.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+            let (iterations, rest) = rest.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+            let algorithm = HashAlgorithm::Argon2id {
+                memory_kib: u32::from_le_bytes(*memory_kib),
+                iterations: u32::from_le_bytes(*iterations),
+            };
+            return Ok((algorithm, rest));
+        }
+
+        let algorithm = Self::ALL
+            .into_iter()
+            .find(|a| a.id() == id)
+            .ok_or(WireError::UnknownAlgorithm(id))?;
+        Ok((algorithm, rest))
+    }
+}
+
+impl PowHasher for HashAlgorithm {
+    fn hash(&self, data: &[u8], out: &mut [u8; 64]) -> usize {
+        match *self {
+            HashAlgorithm::Blake2b512 => DigestHasher::<Blake2b512>::new().hash(data, out),
+            HashAlgorithm::Blake2s256 => DigestHasher::<Blake2s256>::new().hash(data, out),
+            HashAlgorithm::Sha256 => DigestHasher::<Sha256>::new().hash(data, out),
+            HashAlgorithm::Sha3_256 => DigestHasher::<Sha3_256>::new().hash(data, out),
+            HashAlgorithm::Keccak256 => DigestHasher::<Keccak256>::new().hash(data, out),
+            HashAlgorithm::Argon2id {
+                memory_kib,
+                iterations,
+            } => Argon2id {
+                memory_kib,
+                iterations,
+            }
+            .hash(data, out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_digests() {
+        let mut out = [0; 64];
+
+        // The first four bytes of each function's digest of "abc"
+        let expected: [(HashAlgorithm, usize, [u8; 4]); 5] = [
+            (HashAlgorithm::Blake2b512, 64, [0xba, 0x80, 0xa5, 0x3f]),
+            (HashAlgorithm::Blake2s256, 32, [0x50, 0x8c, 0x5e, 0x8c]),
+            (HashAlgorithm::Sha256, 32, [0xba, 0x78, 0x16, 0xbf]),
+            (HashAlgorithm::Sha3_256, 32, [0x3a, 0x98, 0x5d, 0xa7]),
+            (HashAlgorithm::Keccak256, 32, [0x4e, 0x03, 0x65, 0x7a]),
+        ];
+
+        for (algorithm, len, prefix) in expected {
+            assert_eq!(algorithm.hash(b"abc", &mut out), len, "{:?}", algorithm);
+            assert_eq!(algorithm.digest_len(), len, "{:?}", algorithm);
+            assert_eq!(out[..4], prefix, "{:?}", algorithm);
+        }
+
+        // The hashers can be used on their own, e.g. by a custom verifier
+        assert_eq!(DigestHasher::<Sha256>::new().hash(b"abc", &mut out), 32);
+        assert_eq!(out[..4], [0xba, 0x78, 0x16, 0xbf]);
+    }
+
+    #[test]
+    fn argon2id_costs() {
+        let (mut a, mut b) = ([0; 64], [0; 64]);
+        let cheap = HashAlgorithm::Argon2id {
+            memory_kib: 64,
+            iterations: 1,
+        };
+        let costly = HashAlgorithm::Argon2id {
+            memory_kib: 128,
+            iterations: 1,
+        };
+
+        assert_eq!(cheap.hash(b"abc", &mut a), 32);
+        assert_eq!(costly.hash(b"abc", &mut b), 32);
+        assert_ne!(a, b);
+
+        let hasher = Argon2id {
+            memory_kib: 64,
+            iterations: 1,
+        };
+        hasher.hash(b"abc", &mut b);
+        assert_eq!(a, b);
+
+        // Below the minimum cost, which is clamped
+        let clamped = HashAlgorithm::Argon2id {
+            memory_kib: 0,
+            iterations: 0,
+        };
+        clamped.hash(b"abc", &mut a);
+    }
+}
@@ -0,0 +1,384 @@
+use crate::policy::Conditions;
+use crate::{
+    create_challenge_with_context, verify_solution_strict_with_context, Challenge, Difficulty,
+    DifficultyPolicy, HashAlgorithm, InvalidPuzzle, Puzzle, Solution, VerifyError,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A challenge stamped by a [`ChallengeIssuer`]. This is what gets sent to the
+/// client, which must hand `id` back together with its [`Solution`].
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IssuedChallenge {
+    pub id: u128,
+    /// Seconds since the Unix epoch
+    pub issued_at: u64,
+    /// Seconds after `issued_at` during which the challenge can be redeemed
+    pub ttl: u64,
+    pub challenge: Challenge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedeemError {
+    /// The ID was never issued, or has been forgotten after expiring
+    Unknown,
+    Expired,
+    AlreadyUsed,
+    /// The challenge is bound to a context, but none was given to check the
+    /// solution in
+    MissingContext,
+    /// The solution does not solve the challenge
+    Invalid(VerifyError),
+}
+
+impl fmt::Display for RedeemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RedeemError::Unknown => write!(f, "unknown challenge"),
+            RedeemError::Expired => write!(f, "challenge expired"),
+            RedeemError::AlreadyUsed => write!(f, "challenge already redeemed"),
+            RedeemError::MissingContext => write!(f, "challenge needs a context"),
+            RedeemError::Invalid(e) => write!(f, "invalid solution: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for RedeemError {}
+
+struct Entry {
+    challenge: Challenge,
+    expires: Instant,
+    redeemed: bool,
+}
+
+// Every TTL is the same, so challenges expire in the order they were issued
+#[derive(Default)]
+struct Entries {
+    by_id: HashMap<u128, Entry>,
+    queue: VecDeque<(Instant, u128)>,
+}
+
+impl Entries {
+    // Forgets the oldest challenges until none has expired and at most `max`
+    // are left
+    fn forget(&mut self, now: Instant, max: usize) {
+        while let Some(&(expires, id)) = self.queue.front() {
+            if expires > now && self.by_id.len() <= max {
+                break;
+            }
+
+            self.queue.pop_front();
+            // Unless the entry went already and its ID got reused
+            if self.by_id.get(&id).is_some_and(|e| e.expires == expires) {
+                self.by_id.remove(&id);
+            }
+        }
+    }
+}
+
+/// Issues challenges and remembers them until they expire, so that every
+/// challenge can be redeemed at most once.
+pub struct ChallengeIssuer {
+    ttl: Duration,
+    algorithm: HashAlgorithm,
+    puzzle: Puzzle,
+    max_outstanding: usize,
+    entries: Mutex<Entries>,
+}
+
+impl ChallengeIssuer {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            algorithm: HashAlgorithm::default(),
+            puzzle: Puzzle::default(),
+            max_outstanding: 1 << 20,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Remembers at most `max` challenges, about a million by default. Past
+    /// that, issuing a challenge forgets the oldest one.
+    pub fn with_max_outstanding(mut self, max: usize) -> Self {
+        self.max_outstanding = max.max(1);
+        self
+    }
+
+    /// Issues challenges that must be solved with `algorithm`
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Issues challenges whose fragments ask for `puzzle` rather than a hash
+    /// below the target, unless it has no solutions
+    pub fn with_puzzle(mut self, puzzle: Puzzle) -> Result<Self, InvalidPuzzle> {
+        puzzle.validate()?;
+        self.puzzle = puzzle;
+        Ok(self)
+    }
+
+    pub fn issue(
+        &self,
+        difficulty: impl Into<Difficulty>,
+        num_fragments: usize,
+    ) -> IssuedChallenge {
+        self.issue_with_context(difficulty, num_fragments, &[])
+    }
+
+    /// Issues a challenge that can only be redeemed by a request with the same
+    /// `context`, see [`create_challenge_with_context`]
+    pub fn issue_with_context(
+        &self,
+        difficulty: impl Into<Difficulty>,
+        num_fragments: usize,
+        context: &[u8],
+    ) -> IssuedChallenge {
+        let challenge = create_challenge_with_context(difficulty, num_fragments, context)
+            .with_algorithm(self.algorithm)
+            .with_puzzle(self.puzzle)
+            .expect("validated by with_puzzle");
+        let now = Instant::now();
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.forget(now, self.max_outstanding - 1);
+
+        // IDs are random so that they can't be guessed ahead of time
+        let mut id = rand::thread_rng().gen();
+        while entries.by_id.contains_key(&id) {
+            id = rand::thread_rng().gen();
+        }
+
+        let expires = now + self.ttl;
+        entries.by_id.insert(
+            id,
+            Entry {
+                challenge: challenge.clone(),
+                expires,
+                redeemed: false,
+            },
+        );
+        entries.queue.push_back((expires, id));
+
+        IssuedChallenge {
+            id,
+            issued_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            ttl: self.ttl.as_secs(),
+            challenge,
+        }
+    }
+
+    /// Issues a challenge for `context`, as hard as `policy` chooses under
+    /// `conditions`
+    pub fn issue_with_policy(
+        &self,
+        policy: &dyn DifficultyPolicy,
+        conditions: &Conditions,
+        context: &[u8],
+    ) -> IssuedChallenge {
+        let (difficulty, num_fragments) = policy.choose(conditions);
+        self.issue_with_context(difficulty, num_fragments, context)
+    }
+
+    /// Checks `solution` against the challenge issued under `id` and marks it
+    /// as used. A challenge stays redeemable if the solution was invalid.
+    ///
+    /// Only for challenges issued without a context. Others fail with
+    /// [`RedeemError::MissingContext`], as anyone holding the ID could
+    /// redeem them otherwise.
+    pub fn redeem(&self, id: u128, solution: &Solution) -> Result<(), RedeemError> {
+        self.redeem_in(id, solution, None)
+    }
+
+    /// Like [`redeem`](Self::redeem), with `context` taken from the request
+    /// presenting the solution, so that a solution computed by one client
+    /// fails for any other
+    pub fn redeem_with_context(
+        &self,
+        id: u128,
+        solution: &Solution,
+        context: &[u8],
+    ) -> Result<(), RedeemError> {
+        self.redeem_in(id, solution, Some(context))
+    }
+
+    // Redeems in `context`, which only context-free challenges may go without
+    fn redeem_in(
+        &self,
+        id: u128,
+        solution: &Solution,
+        context: Option<&[u8]>,
+    ) -> Result<(), RedeemError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.by_id.get_mut(&id).ok_or(RedeemError::Unknown)?;
+
+        if entry.redeemed {
+            return Err(RedeemError::AlreadyUsed);
+        }
+
+        if entry.expires <= Instant::now() {
+            entries.by_id.remove(&id);
+            return Err(RedeemError::Expired);
+        }
+
+        let context = match context {
+            Some(context) => context,
+            None if entry.challenge.context.is_empty() => &[],
+            None => return Err(RedeemError::MissingContext),
+        };
+        verify_solution_strict_with_context(&entry.challenge, solution, context)
+            .map_err(RedeemError::Invalid)?;
+
+        entry.redeemed = true;
+        Ok(())
+    }
+
+    /// Forgets every challenge whose TTL has run out. Issuing does so too.
+    pub fn purge_expired(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.forget(Instant::now(), usize::MAX);
+    }
+}
+
+#[cfg(all(test, feature = "solve"))]
+mod tests {
+    use super::*;
+    use crate::policy::LinearBackoff;
+    use crate::{solve_challenge_blocking, NoProgress, SolveOptions};
+
+    fn solve(challenge: &Challenge) -> Solution {
+        solve_challenge_blocking(challenge, &NoProgress, &SolveOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn redeems_once() {
+        let issuer = ChallengeIssuer::new(Duration::from_secs(60));
+        let issued = issuer.issue(1000, 2);
+        let solution = solve(&issued.challenge);
+
+        assert_eq!(
+            issuer.redeem(issued.id.wrapping_add(1), &solution),
+            Err(RedeemError::Unknown)
+        );
+        let other = issuer.issue(1000, 2);
+        assert_eq!(
+            issuer.redeem(other.id, &solution),
+            Err(RedeemError::Invalid(VerifyError::DifferentChallenge(0)))
+        );
+
+        // Solutions must come the way solvers produce them
+        let mut reordered = solution.clone();
+        reordered.proofs.reverse();
+        assert_eq!(
+            issuer.redeem(issued.id, &reordered),
+            Err(RedeemError::Invalid(VerifyError::OutOfOrder(0)))
+        );
+        assert_eq!(issuer.redeem(issued.id, &solution), Ok(()));
+        assert_eq!(
+            issuer.redeem(issued.id, &solution),
+            Err(RedeemError::AlreadyUsed)
+        );
+    }
+
+    #[test]
+    fn follows_policy() {
+        let issuer = ChallengeIssuer::new(Duration::from_secs(60));
+        let policy = LinearBackoff {
+            hashes_per_second: 1e6,
+            solve_time: Duration::from_millis(1),
+            step: Duration::from_millis(1),
+            max: Duration::from_secs(1),
+        };
+        let conditions = Conditions {
+            failures: 2,
+            ..Default::default()
+        };
+
+        let issued = issuer.issue_with_policy(&policy, &conditions, b"client");
+        let (difficulty, fragments) = Difficulty::for_solve_time(1e6, Duration::from_millis(3));
+        assert_eq!(issued.challenge.difficulty(), difficulty);
+        assert_eq!(
+            issued.challenge.expected_attempts(),
+            fragments as f64 * difficulty.expected_attempts()
+        );
+        assert_eq!(
+            issuer.redeem_with_context(issued.id, &solve(&issued.challenge), b"client"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn redeems_in_issued_context() {
+        let issuer = ChallengeIssuer::new(Duration::from_secs(60));
+        let issued = issuer.issue_with_context(Difficulty::LeadingZeroBits(16), 1, b"/login");
+        let solution = solve(&issued.challenge);
+
+        assert_eq!(
+            issuer.redeem_with_context(issued.id, &solution, b"/other"),
+            Err(RedeemError::Invalid(VerifyError::InvalidNonce(0)))
+        );
+        assert_eq!(
+            issuer.redeem(issued.id, &solution),
+            Err(RedeemError::MissingContext)
+        );
+        assert_eq!(
+            issuer.redeem_with_context(issued.id, &solution, b"/login"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn issues_puzzles() {
+        let puzzle = Puzzle::CuckooCycle {
+            edge_bits: 12,
+            cycle_len: 8,
+        };
+        let issuer = ChallengeIssuer::new(Duration::from_secs(60))
+            .with_puzzle(puzzle)
+            .unwrap();
+        let issued = issuer.issue(Difficulty::LeadingZeroBits(2), 2);
+        let solution = solve(&issued.challenge);
+
+        assert_eq!(issued.challenge.puzzle, puzzle);
+        assert_eq!(issuer.redeem(issued.id, &solution), Ok(()));
+    }
+
+    #[test]
+    fn forgets_oldest() {
+        let issuer = ChallengeIssuer::new(Duration::from_secs(60)).with_max_outstanding(2);
+        let first = issuer.issue(1000, 1);
+        let solution = solve(&first.challenge);
+        let (second, third) = (issuer.issue(1000, 1), issuer.issue(1000, 1));
+
+        assert_eq!(
+            issuer.redeem(first.id, &solution),
+            Err(RedeemError::Unknown)
+        );
+        assert_eq!(issuer.redeem(second.id, &solve(&second.challenge)), Ok(()));
+        assert_eq!(issuer.redeem(third.id, &solve(&third.challenge)), Ok(()));
+        assert_eq!(issuer.entries.lock().unwrap().queue.len(), 2);
+    }
+
+    #[test]
+    fn rejects_expired() {
+        let issuer = ChallengeIssuer::new(Duration::ZERO);
+        let issued = issuer.issue(1000, 1);
+        let solution = solve(&issued.challenge);
+
+        assert_eq!(
+            issuer.redeem(issued.id, &solution),
+            Err(RedeemError::Expired)
+        );
+        assert_eq!(
+            issuer.redeem(issued.id, &solution),
+            Err(RedeemError::Unknown)
+        );
+    }
+}
@@ -0,0 +1,445 @@
+//! BLAKE2b-512 over several nonces at once, for the solver. Every word of the
+//! state holds one lane per nonce, side by side in an AVX2 register where the
+//! CPU has one, or in two NEON registers on ARM.
+//!
+//! The fragment shares its block with the nonce, so there's no compressed
+//! prefix to reuse. What gets built once per fragment instead is the message,
+//! already padded and split into words, leaving only the nonce to fill in.
+
+use crate::Difficulty;
+use core::ops::Range;
+
+/// Nonces hashed per call
+const LANES: usize = 4;
+
+// One word for every lane
+type Lanes = [u64; LANES];
+
+const BLOCK_LEN: usize = 128;
+
+const IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SIGMA: [[usize; 16]; 12] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+];
+
+/// Hashes `fragment || nonce || context` exactly like
+/// [`HashAlgorithm::Blake2b512`](crate::HashAlgorithm::Blake2b512), for
+/// [`LANES`] consecutive nonces at a time
+pub(crate) struct Blake2bLanes {
+    // Every block of the message, the nonce words of the first left at zero
+    blocks: Vec<[u64; 16]>,
+    len: usize,
+    #[cfg(target_arch = "x86_64")]
+    avx2: bool,
+}
+
+impl Blake2bLanes {
+    pub(crate) fn new(fragment: [u8; 16], context: &[u8]) -> Self {
+        let message = [&fragment[..], &[0; 16], context].concat();
+        let blocks = message
+            .chunks(BLOCK_LEN)
+            .map(|chunk| {
+                let mut block = [0; BLOCK_LEN];
+                block[..chunk.len()].copy_from_slice(chunk);
+
+                let mut words = [0; 16];
+                for (word, bytes) in words.iter_mut().zip(block.chunks_exact(8)) {
+                    *word = u64::from_le_bytes(bytes.try_into().unwrap());
+                }
+                words
+            })
+            .collect();
+
+        Self {
+            blocks,
+            len: message.len(),
+            #[cfg(target_arch = "x86_64")]
+            avx2: std::is_x86_feature_detected!("avx2"),
+        }
+    }
+
+    /// The first nonce in `nonces` whose hash meets `difficulty`
+    pub(crate) fn find(&self, nonces: Range<u128>, difficulty: &Difficulty) -> Option<u128> {
+        let mut digests = [[0; 64]; LANES];
+        for first in nonces.clone().step_by(LANES) {
+            self.hash(first, &mut digests);
+            let found = (first..nonces.end)
+                .zip(&digests)
+                .find(|(_, digest)| difficulty.meets(&digest[..]));
+            if let Some((nonce, _)) = found {
+                return Some(nonce);
+            }
+        }
+        None
+    }
+
+    /// Writes the digests for the nonces from `first` on, one per lane
+    pub(crate) fn hash(&self, first: u128, out: &mut [[u8; 64]; LANES]) {
+        #[cfg(target_arch = "x86_64")]
+        if self.avx2 {
+            // SAFETY: the CPU was found to support AVX2
+            return unsafe { self.hash_avx2(first, out) };
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        return self.hash_with::<neon::Neon>(first, out);
+        #[cfg(not(target_arch = "aarch64"))]
+        self.hash_with::<Lanes>(first, out)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn hash_avx2(&self, first: u128, out: &mut [[u8; 64]; LANES]) {
+        self.hash_with::<avx2::Avx2>(first, out)
+    }
+
+    #[inline(always)]
+    fn hash_with<W: Word>(&self, first: u128, out: &mut [[u8; 64]; LANES]) {
+        // Parameter block: 64-byte digest, no key, sequential mode
+        let mut h = IV.map(W::splat);
+        h[0] = W::splat(IV[0] ^ 0x01010040);
+
+        let last = self.blocks.len() - 1;
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut m = block.map(W::splat);
+            if i == 0 {
+                let nonces: [u128; LANES] =
+                    core::array::from_fn(|lane| first.wrapping_add(lane as u128));
+                m[2] = W::from_lanes(nonces.map(|nonce| nonce as u64));
+                m[3] = W::from_lanes(nonces.map(|nonce| (nonce >> 64) as u64));
+            }
+
+            let counter = self.len.min((i + 1) * BLOCK_LEN) as u64;
+            compress(&mut h, &m, counter, i == last);
+        }
+
+        let h = h.map(W::to_lanes);
+        for (lane, digest) in out.iter_mut().enumerate() {
+            for (word, bytes) in h.iter().zip(digest.chunks_exact_mut(8)) {
+                bytes.copy_from_slice(&word[lane].to_le_bytes());
+            }
+        }
+    }
+}
+
+/// One word of the state for every lane, and the only operations BLAKE2b
+/// needs on it
+trait Word: Copy {
+    fn splat(word: u64) -> Self;
+    fn from_lanes(lanes: Lanes) -> Self;
+    fn to_lanes(self) -> Lanes;
+    fn add(self, other: Self) -> Self;
+    fn xor(self, other: Self) -> Self;
+    fn ror32(self) -> Self;
+    fn ror24(self) -> Self;
+    fn ror16(self) -> Self;
+    fn ror63(self) -> Self;
+}
+
+// Plain arrays, for CPUs without any of the vector units below
+impl Word for Lanes {
+    fn splat(word: u64) -> Self {
+        [word; LANES]
+    }
+
+    fn from_lanes(lanes: Lanes) -> Self {
+        lanes
+    }
+
+    fn to_lanes(self) -> Lanes {
+        self
+    }
+
+    #[inline(always)]
+    fn add(self, other: Self) -> Self {
+        core::array::from_fn(|i| self[i].wrapping_add(other[i]))
+    }
+
+    #[inline(always)]
+    fn xor(self, other: Self) -> Self {
+        core::array::from_fn(|i| self[i] ^ other[i])
+    }
+
+    #[inline(always)]
+    fn ror32(self) -> Self {
+        self.map(|word| word.rotate_right(32))
+    }
+
+    #[inline(always)]
+    fn ror24(self) -> Self {
+        self.map(|word| word.rotate_right(24))
+    }
+
+    #[inline(always)]
+    fn ror16(self) -> Self {
+        self.map(|word| word.rotate_right(16))
+    }
+
+    #[inline(always)]
+    fn ror63(self) -> Self {
+        self.map(|word| word.rotate_right(63))
+    }
+}
+
+// Only ever used from functions compiled with AVX2 enabled
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{Lanes, Word};
+    use core::arch::x86_64::*;
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Avx2(__m256i);
+
+    impl Word for Avx2 {
+        #[inline(always)]
+        fn splat(word: u64) -> Self {
+            Avx2(unsafe { _mm256_set1_epi64x(word as i64) })
+        }
+
+        #[inline(always)]
+        fn from_lanes(lanes: Lanes) -> Self {
+            Avx2(unsafe { _mm256_loadu_si256(lanes.as_ptr().cast()) })
+        }
+
+        #[inline(always)]
+        fn to_lanes(self) -> Lanes {
+            let mut lanes = [0; 4];
+            unsafe { _mm256_storeu_si256(lanes.as_mut_ptr().cast(), self.0) };
+            lanes
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            Avx2(unsafe { _mm256_add_epi64(self.0, other.0) })
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            Avx2(unsafe { _mm256_xor_si256(self.0, other.0) })
+        }
+
+        // Rotations by whole bytes are shuffles, and by 63 a shift and an add
+        #[inline(always)]
+        fn ror32(self) -> Self {
+            Avx2(unsafe { _mm256_shuffle_epi32::<0b10_11_00_01>(self.0) })
+        }
+
+        #[inline(always)]
+        fn ror24(self) -> Self {
+            Avx2(unsafe {
+                let bytes = _mm256_setr_epi8(
+                    3, 4, 5, 6, 7, 0, 1, 2, 11, 12, 13, 14, 15, 8, 9, 10, 3, 4, 5, 6, 7, 0, 1, 2,
+                    11, 12, 13, 14, 15, 8, 9, 10,
+                );
+                _mm256_shuffle_epi8(self.0, bytes)
+            })
+        }
+
+        #[inline(always)]
+        fn ror16(self) -> Self {
+            Avx2(unsafe {
+                let bytes = _mm256_setr_epi8(
+                    2, 3, 4, 5, 6, 7, 0, 1, 10, 11, 12, 13, 14, 15, 8, 9, 2, 3, 4, 5, 6, 7, 0, 1,
+                    10, 11, 12, 13, 14, 15, 8, 9,
+                );
+                _mm256_shuffle_epi8(self.0, bytes)
+            })
+        }
+
+        #[inline(always)]
+        fn ror63(self) -> Self {
+            Avx2(unsafe {
+                _mm256_or_si256(
+                    _mm256_srli_epi64::<63>(self.0),
+                    _mm256_add_epi64(self.0, self.0),
+                )
+            })
+        }
+    }
+}
+
+// Two 128-bit registers. NEON comes with every 64-bit ARM CPU.
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::{Lanes, Word};
+    use core::arch::aarch64::*;
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Neon([uint64x2_t; 2]);
+
+    impl Neon {
+        #[inline(always)]
+        fn each(self, f: impl Fn(uint64x2_t) -> uint64x2_t) -> Self {
+            Neon(self.0.map(f))
+        }
+
+        #[inline(always)]
+        fn zip(self, other: Self, f: impl Fn(uint64x2_t, uint64x2_t) -> uint64x2_t) -> Self {
+            Neon([f(self.0[0], other.0[0]), f(self.0[1], other.0[1])])
+        }
+    }
+
+    impl Word for Neon {
+        #[inline(always)]
+        fn splat(word: u64) -> Self {
+            Neon([unsafe { vdupq_n_u64(word) }; 2])
+        }
+
+        #[inline(always)]
+        fn from_lanes(lanes: Lanes) -> Self {
+            unsafe { Neon([vld1q_u64(lanes.as_ptr()), vld1q_u64(lanes[2..].as_ptr())]) }
+        }
+
+        #[inline(always)]
+        fn to_lanes(self) -> Lanes {
+            let mut lanes = [0; 4];
+            unsafe {
+                vst1q_u64(lanes.as_mut_ptr(), self.0[0]);
+                vst1q_u64(lanes[2..].as_mut_ptr(), self.0[1]);
+            }
+            lanes
+        }
+
+        #[inline(always)]
+        fn add(self, other: Self) -> Self {
+            self.zip(other, |a, b| unsafe { vaddq_u64(a, b) })
+        }
+
+        #[inline(always)]
+        fn xor(self, other: Self) -> Self {
+            self.zip(other, |a, b| unsafe { veorq_u64(a, b) })
+        }
+
+        #[inline(always)]
+        fn ror32(self) -> Self {
+            self.each(|a| unsafe { vreinterpretq_u64_u32(vrev64q_u32(vreinterpretq_u32_u64(a))) })
+        }
+
+        #[inline(always)]
+        fn ror24(self) -> Self {
+            self.each(|a| unsafe { vsriq_n_u64::<24>(vshlq_n_u64::<40>(a), a) })
+        }
+
+        #[inline(always)]
+        fn ror16(self) -> Self {
+            self.each(|a| unsafe { vsriq_n_u64::<16>(vshlq_n_u64::<48>(a), a) })
+        }
+
+        #[inline(always)]
+        fn ror63(self) -> Self {
+            self.each(|a| unsafe { vsriq_n_u64::<63>(vshlq_n_u64::<1>(a), a) })
+        }
+    }
+}
+
+#[inline(always)]
+fn compress<W: Word>(h: &mut [W; 8], m: &[W; 16], counter: u64, last: bool) {
+    let mut v = [W::splat(0); 16];
+    v[..8].copy_from_slice(h);
+    for (i, word) in IV.iter().enumerate() {
+        v[8 + i] = W::splat(*word);
+    }
+    v[12] = v[12].xor(W::splat(counter));
+    if last {
+        v[14] = v[14].xor(W::splat(u64::MAX));
+    }
+
+    for s in &SIGMA {
+        g(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        g(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        g(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        g(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        g(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        g(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        g(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        g(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+
+    for i in 0..8 {
+        h[i] = h[i].xor(v[i]).xor(v[i + 8]);
+    }
+}
+
+#[inline(always)]
+fn g<W: Word>(v: &mut [W; 16], a: usize, b: usize, c: usize, d: usize, x: W, y: W) {
+    v[a] = v[a].add(v[b]).add(x);
+    v[d] = v[d].xor(v[a]).ror32();
+    v[c] = v[c].add(v[d]);
+    v[b] = v[b].xor(v[c]).ror24();
+    v[a] = v[a].add(v[b]).add(y);
+    v[d] = v[d].xor(v[a]).ror16();
+    v[c] = v[c].add(v[d]);
+    v[b] = v[b].xor(v[c]).ror63();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HashAlgorithm, PowHasher};
+
+    #[test]
+    fn matches_blake2b() {
+        let fragment = [0x5a; 16];
+        let mut expected = [0; 64];
+        let (mut digests, mut portable) = ([[0; 64]; LANES], [[0; 64]; LANES]);
+
+        // Around every block boundary, and with nonces overflowing 64 bits
+        for context_len in [0, 1, 95, 96, 97, 223, 224, 225, 500] {
+            let context: Vec<u8> = (0..context_len).map(|i| i as u8).collect();
+            let lanes = Blake2bLanes::new(fragment, &context);
+
+            for first in [0, 1000, u64::MAX as u128 - 1, u128::MAX - 3] {
+                lanes.hash(first, &mut digests);
+                lanes.hash_with::<Lanes>(first, &mut portable);
+                assert_eq!(digests, portable);
+
+                for (i, digest) in digests.iter().enumerate() {
+                    let nonce = first + i as u128;
+                    let data = [&fragment[..], &nonce.to_le_bytes(), &context].concat();
+                    HashAlgorithm::Blake2b512.hash(&data, &mut expected);
+                    assert_eq!(digest, &expected, "{context_len} {nonce}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_the_first_nonce() {
+        let difficulty = Difficulty::LeadingZeroBits(6);
+        let lanes = Blake2bLanes::new([1; 16], b"ctx");
+        let mut hash = [0; 64];
+        let mut meets = |nonce: u128| {
+            let data = [&[1; 16][..], &nonce.to_le_bytes(), b"ctx"].concat();
+            HashAlgorithm::Blake2b512.hash(&data, &mut hash);
+            difficulty.meets(&hash)
+        };
+
+        let first = (0..).find(|&n| meets(n)).unwrap();
+        assert_eq!(lanes.find(0..1024, &difficulty), Some(first));
+
+        // Nonces past the end of the range don't count
+        assert_eq!(lanes.find(0..first, &difficulty), None);
+        assert_eq!(lanes.find(first..first + 1, &difficulty), Some(first));
+    }
+}
@@ -1,12 +1,58 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 extern crate core;
 
-use crypto_hashes::blake2::{Blake2b512, Digest};
-use rand::Rng;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter;
+use core::time::Duration;
+use rand::{Rng, RngCore};
 use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
-use std::iter;
-use tokio::sync::broadcast::Sender;
-use tokio::task::JoinSet;
+
+mod cuckoo;
+mod difficulty;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod hasher;
+#[cfg(feature = "std")]
+mod issuer;
+#[cfg(feature = "solve")]
+mod lanes;
+#[cfg(feature = "std")]
+pub mod limiter;
+#[cfg(feature = "std")]
+pub mod policy;
+mod progress;
+mod puzzle;
+#[cfg(feature = "solve")]
+mod solve;
+mod text;
+mod timelock;
+#[cfg(feature = "std")]
+mod token;
+mod wire;
+
+pub use difficulty::Difficulty;
+pub use hasher::{Argon2id, DigestHasher, HashAlgorithm, PowHasher};
+#[cfg(feature = "std")]
+pub use issuer::{ChallengeIssuer, IssuedChallenge, RedeemError};
+#[cfg(feature = "std")]
+pub use limiter::RateLimiter;
+#[cfg(feature = "std")]
+pub use policy::DifficultyPolicy;
+pub use progress::{Callback, NoProgress, Progress, ProgressSink};
+pub use puzzle::{InvalidPuzzle, Puzzle};
+#[cfg(feature = "solve")]
+pub use solve::{
+    calibrate, solve_challenge_blocking, CancelToken, SolveError, SolveOptions, SolverBackend,
+};
+#[cfg(feature = "tokio")]
+pub use solve::{solve_challenge, solve_challenge_with};
+pub use text::ParseError;
+#[cfg(feature = "std")]
+pub use token::{ChallengeSigner, ChallengeToken, TokenError};
+pub use wire::WireError;
 
 #[repr(C)]
 pub struct PowHash {
@@ -14,109 +60,348 @@ pub struct PowHash {
     data: *const u8,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Challenge {
-    difficulty: u32,
+    difficulty: Difficulty,
     fragments: Vec<[u8; 16]>,
+    // Opaque client data (IP, session, request path...) mixed into every hash
+    #[serde(default)]
+    context: Vec<u8>,
+    #[serde(default)]
+    algorithm: HashAlgorithm,
+    #[serde(default)]
+    puzzle: Puzzle,
+}
+
+#[cfg(feature = "std")]
+pub fn create_challenge(difficulty: impl Into<Difficulty>, num_fragments: usize) -> Challenge {
+    create_challenge_with_context(difficulty, num_fragments, &[])
+}
+
+/// Creates a challenge whose solutions are only valid for `context`
+#[cfg(feature = "std")]
+pub fn create_challenge_with_context(
+    difficulty: impl Into<Difficulty>,
+    num_fragments: usize,
+    context: &[u8],
+) -> Challenge {
+    create_challenge_with_rng(&mut rand::thread_rng(), difficulty, num_fragments, context)
 }
 
-pub fn create_challenge(difficulty: u32, num_fragments: usize) -> Challenge {
+/// Creates a challenge with fragments drawn from `rng`, which should be a
+/// cryptographically secure generator. Works without `std`.
+pub fn create_challenge_with_rng(
+    rng: &mut impl RngCore,
+    difficulty: impl Into<Difficulty>,
+    num_fragments: usize,
+    context: &[u8],
+) -> Challenge {
     // Challenge fragments are 16 bytes of random data
-    let fragments = iter::from_fn(|| Some(rand::thread_rng().gen()))
+    let fragments = iter::from_fn(|| Some(rng.gen()))
         .take(num_fragments)
         .collect();
 
     Challenge {
-        difficulty,
+        difficulty: difficulty.into(),
         fragments,
+        context: context.to_vec(),
+        algorithm: HashAlgorithm::default(),
+        puzzle: Puzzle::default(),
     }
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct Solution {
-    proofs: Vec<([u8; 16], u128)>,
-}
+impl Challenge {
+    /// Switches the hash function that proofs must be computed with
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Switches the kind of work each fragment asks for, unless `puzzle` has
+    /// no solutions. A [`TimeLock`](Puzzle::TimeLock) keeps a single fragment,
+    /// as any more would run side by side on a client with the cores for it.
+    pub fn with_puzzle(mut self, puzzle: Puzzle) -> Result<Self, InvalidPuzzle> {
+        puzzle.validate()?;
+        if let Puzzle::TimeLock { .. } = puzzle {
+            self.fragments.truncate(1);
+        }
+        self.puzzle = puzzle;
+        Ok(self)
+    }
 
-pub async fn solve_challenge(challenge: &Challenge, progress: &Sender<u128>) -> Solution {
-    let mut set = JoinSet::new();
-    for x in &challenge.fragments {
-        set.spawn(solve_fragment(x.clone(), challenge.difficulty));
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
     }
 
-    let mut proofs = vec![];
-    while let Some(res) = set.join_next().await {
-        let solution = res.unwrap();
-        proofs.push(solution);
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub fn puzzle(&self) -> Puzzle {
+        self.puzzle
+    }
+
+    /// Mean number of attempts needed to solve every fragment
+    pub fn expected_attempts(&self) -> f64 {
+        self.attempts_per_fragment() * self.fragments.len() as f64
+    }
+
+    fn attempts_per_fragment(&self) -> f64 {
+        let difficulty = 1.0
+            / self
+                .difficulty
+                .success_probability_for(self.algorithm.digest_len());
+        match self.puzzle {
+            Puzzle::TimeLock { .. } => self.puzzle.attempts_per_success(),
+            _ => self.puzzle.attempts_per_success() * difficulty,
+        }
+    }
 
-        // Notify of progress
-        progress.send(solution.1).unwrap();
+    /// Mean time to solve every fragment at `hashes_per_second`, which counts
+    /// attempts for puzzles other than [`Puzzle::HashTarget`]
+    pub fn estimated_solve_time(&self, hashes_per_second: f64) -> Duration {
+        Duration::try_from_secs_f64(self.expected_attempts() / hashes_per_second)
+            .unwrap_or(Duration::MAX)
     }
+}
 
-    Solution { proofs }
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Solution {
+    proofs: Vec<([u8; 16], u128)>,
+    // What the puzzle needs besides the nonce, one per proof. Empty for
+    // `Puzzle::HashTarget`.
+    #[serde(default)]
+    witnesses: Vec<Vec<u8>>,
 }
 
-async fn solve_fragment(fragment: [u8; 16], difficulty: u32) -> ([u8; 16], u128) {
-    let mut nonce: u128 = 0;
+impl Solution {
+    fn from_proofs(proofs: Vec<([u8; 16], u128, Vec<u8>)>) -> Self {
+        let (proofs, mut witnesses): (_, Vec<Vec<u8>>) =
+            proofs.into_iter().map(|(f, n, w)| ((f, n), w)).unzip();
 
-    loop {
-        if hash_found(fragment, difficulty, nonce) {
-            return (fragment, nonce);
+        // Hash targets have no witnesses, and leave them out entirely
+        if witnesses.iter().all(Vec::is_empty) {
+            witnesses.clear();
         }
+        Solution { proofs, witnesses }
+    }
 
-        nonce += 1;
+    fn witness(&self, i: usize) -> &[u8] {
+        self.witnesses.get(i).map_or(&[], Vec::as_slice)
     }
 }
 
-fn hash_found(fragment: [u8; 16], difficulty: u32, nonce: u128) -> bool {
-    let mut hasher = Blake2b512::new();
-    hasher.update([fragment, nonce.to_le_bytes()].concat());
-    let hash = hasher.finalize();
-    let first_four_bytes: [u8; 4] = hash[0..4].try_into().unwrap();
+/// Why a solution was rejected. Indices count proofs in the solution, or
+/// fragments in the challenge for `MissingFragment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+    /// A proof is for a fragment the challenge doesn't have, so the solution
+    /// was most likely computed for another challenge
+    DifferentChallenge(usize),
+    /// A fragment of the challenge has no proof
+    MissingFragment(usize),
+    /// A proof's nonce, or the witness that goes with it, doesn't solve its
+    /// fragment
+    InvalidNonce(usize),
+    /// The solution is inconsistent in itself, which honest solvers never
+    /// produce
+    Malformed,
+    /// Strict verification only: the solution has this many proofs, more than
+    /// the challenge has fragments
+    TooManyProofs(usize),
+    /// Strict verification only: a proof repeats the fragment of an earlier one
+    DuplicateProof(usize),
+    /// Strict verification only: a proof is for a fragment further along in
+    /// the challenge
+    OutOfOrder(usize),
+}
 
-    u32::from_be_bytes(first_four_bytes) < (u32::MAX - difficulty)
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::DifferentChallenge(i) => write!(f, "proof {i} is for another challenge"),
+            VerifyError::MissingFragment(i) => write!(f, "fragment {i} has no proof"),
+            VerifyError::InvalidNonce(i) => write!(f, "proof {i} has an invalid nonce"),
+            VerifyError::Malformed => write!(f, "malformed solution"),
+            VerifyError::TooManyProofs(n) => write!(f, "too many proofs ({n})"),
+            VerifyError::DuplicateProof(i) => write!(f, "proof {i} is a duplicate"),
+            VerifyError::OutOfOrder(i) => write!(f, "proof {i} is out of order"),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for VerifyError {}
+
 pub fn verify_solution(challenge: &Challenge, solution: &Solution) -> bool {
+    try_verify_solution(challenge, solution).is_ok()
+}
+
+/// Verifies `solution` as if it had been computed for `context`, which should
+/// come from the request presenting the solution rather than the challenge.
+pub fn verify_solution_with_context(
+    challenge: &Challenge,
+    solution: &Solution,
+    context: &[u8],
+) -> bool {
+    try_verify_solution_with_context(challenge, solution, context).is_ok()
+}
+
+/// Like [`verify_solution`], telling why the solution was rejected
+pub fn try_verify_solution(challenge: &Challenge, solution: &Solution) -> Result<(), VerifyError> {
+    try_verify_solution_with_context(challenge, solution, &challenge.context)
+}
+
+/// Like [`verify_solution_with_context`], telling why the solution was
+/// rejected
+pub fn try_verify_solution_with_context(
+    challenge: &Challenge,
+    solution: &Solution,
+    context: &[u8],
+) -> Result<(), VerifyError> {
+    check_witnesses(solution)?;
+
     // Does the solution correspond to the challenge
-    for f in &challenge.fragments {
-        if solution.proofs.iter().find(|p| &p.0 == f).is_none() {
-            return false;
-        }
+    let index: FragmentIndex = challenge
+        .fragments
+        .iter()
+        .enumerate()
+        .map(|(i, f)| (f, i))
+        .collect();
+    let mut proven = alloc::vec![false; challenge.fragments.len()];
+    for (i, p) in solution.proofs.iter().enumerate() {
+        let j = index.get(&p.0).ok_or(VerifyError::DifferentChallenge(i))?;
+        proven[*j] = true;
     }
 
-    for p in &solution.proofs {
-        if !hash_found(p.0, challenge.difficulty, p.1) {
-            return false;
+    if let Some(i) = proven.iter().position(|p| !p) {
+        return Err(VerifyError::MissingFragment(i));
+    }
+
+    check_proofs(challenge, solution, context)
+}
+
+// Where each fragment sits in the challenge. Without std there's no hasher to
+// seed, so lookups cost a logarithm more.
+#[cfg(feature = "std")]
+type FragmentIndex<'a> = std::collections::HashMap<&'a [u8; 16], usize>;
+#[cfg(not(feature = "std"))]
+type FragmentIndex<'a> = alloc::collections::BTreeMap<&'a [u8; 16], usize>;
+
+/// Like [`try_verify_solution`], but only accepts exactly one proof per
+/// fragment, in the order of the challenge. This is what the solvers produce,
+/// and it bounds the work an oversized solution can cause.
+pub fn verify_solution_strict(
+    challenge: &Challenge,
+    solution: &Solution,
+) -> Result<(), VerifyError> {
+    verify_solution_strict_with_context(challenge, solution, &challenge.context)
+}
+
+/// Like [`verify_solution_strict`], with `context` taken from the request
+/// presenting the solution
+pub fn verify_solution_strict_with_context(
+    challenge: &Challenge,
+    solution: &Solution,
+    context: &[u8],
+) -> Result<(), VerifyError> {
+    // Before looking at any proof
+    if solution.proofs.len() > challenge.fragments.len() {
+        return Err(VerifyError::TooManyProofs(solution.proofs.len()));
+    }
+    check_witnesses(solution)?;
+
+    for (i, (p, f)) in solution.proofs.iter().zip(&challenge.fragments).enumerate() {
+        if p.0 != *f {
+            // Only a failing solution pays for this search
+            return Err(match challenge.fragments.iter().position(|f| *f == p.0) {
+                Some(j) if j < i => VerifyError::DuplicateProof(i),
+                Some(_) => VerifyError::OutOfOrder(i),
+                None => VerifyError::DifferentChallenge(i),
+            });
         }
     }
 
-    true
+    if solution.proofs.len() < challenge.fragments.len() {
+        return Err(VerifyError::MissingFragment(solution.proofs.len()));
+    }
+
+    check_proofs(challenge, solution, context)
+}
+
+// Either no proof has a witness, or every one does
+fn check_witnesses(solution: &Solution) -> Result<(), VerifyError> {
+    if !solution.witnesses.is_empty() && solution.witnesses.len() != solution.proofs.len() {
+        return Err(VerifyError::Malformed);
+    }
+    Ok(())
+}
+
+fn check_proofs(
+    challenge: &Challenge,
+    solution: &Solution,
+    context: &[u8],
+) -> Result<(), VerifyError> {
+    for (i, p) in solution.proofs.iter().enumerate() {
+        if !puzzle::check(challenge, p.0, p.1, solution.witness(i), context) {
+            return Err(VerifyError::InvalidNonce(i));
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "tokio")]
     use tokio::runtime::Runtime;
+    #[cfg(feature = "tokio")]
+    use tokio::sync::broadcast::error::RecvError;
+    #[cfg(feature = "tokio")]
     use tokio::sync::broadcast::{Receiver, Sender};
 
+    // Without `solve` there are no fragment workers to run, so this solves
+    // each fragment by hand, trying nonces from zero up
+    fn proofs_by_hand(challenge: &Challenge, context: &[u8]) -> Vec<([u8; 16], u128, Vec<u8>)> {
+        challenge
+            .fragments
+            .iter()
+            .map(|&f| {
+                (0..)
+                    .find_map(|n| {
+                        Some((f, n, puzzle::attempt(challenge, f, n, context, &|_| true)?))
+                    })
+                    .unwrap()
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "tokio")]
     #[tokio::test]
     async fn it_works() {
         let rt = Runtime::new().unwrap();
 
         let num_fragments = 4;
         let challenge = create_challenge(4294940000, num_fragments);
-        let (tx, mut rx): (Sender<u128>, Receiver<u128>) =
+        let (tx, mut rx): (Sender<Progress>, Receiver<Progress>) =
             tokio::sync::broadcast::channel(num_fragments);
 
         rt.spawn(async move {
-            for _ in 0..num_fragments {
-                println!("Broadcast received: {}", rx.recv().await.unwrap());
+            loop {
+                match rx.recv().await {
+                    Ok(progress) => println!("Broadcast received: {:?}", progress),
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
             }
         });
 
         let challenge2 = Challenge {
             difficulty: challenge.difficulty,
             fragments: challenge.fragments.clone(),
+            context: challenge.context.clone(),
+            algorithm: challenge.algorithm,
+            puzzle: challenge.puzzle,
         };
 
         let solution = rt
@@ -124,7 +409,269 @@ mod tests {
             .await
             .unwrap();
 
-        assert_eq!(verify_solution(&challenge, &solution), true);
+        assert!(verify_solution(&challenge, &solution));
         std::mem::forget(rt);
     }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn bound_to_context() {
+        // Roughly one in 256 hashes is good enough, so a wrong context can't
+        // pass by accident
+        let difficulty = u32::MAX - (1 << 24);
+        let challenge = create_challenge_with_context(difficulty, 4, b"203.0.113.7 GET /login");
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+
+        assert!(verify_solution(&challenge, &solution));
+        assert!(verify_solution_with_context(
+            &challenge,
+            &solution,
+            b"203.0.113.7 GET /login"
+        ));
+        assert!(!verify_solution_with_context(
+            &challenge,
+            &solution,
+            b"198.51.100.2 GET /login"
+        ));
+    }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn leading_zero_bits() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(8), 4);
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+
+        assert_eq!(challenge.expected_attempts(), 1024.0);
+        assert!(verify_solution(&challenge, &solution));
+    }
+
+    #[test]
+    fn old_challenges() {
+        // As serialized before difficulty models, algorithms and puzzles
+        let json = r#"{"difficulty":1000,"fragments":[[7,7,7,7,7,7,7,7,7,7,7,7,7,7,7,7]]}"#;
+        let challenge: Challenge = serde_json::from_str(json).unwrap();
+
+        assert_eq!(challenge.difficulty(), Difficulty::Legacy(1000));
+        assert_eq!(challenge.fragments, [[7; 16]]);
+        assert_eq!(challenge.algorithm, HashAlgorithm::default());
+        assert_eq!(challenge.puzzle, Puzzle::default());
+    }
+
+    #[test]
+    fn binary_serde() {
+        let mut rng = rand::rngs::mock::StepRng::new(1, 1);
+        for difficulty in [
+            Difficulty::Legacy(1000),
+            Difficulty::Target(Difficulty::target_for_bits(12)),
+        ] {
+            let challenge = create_challenge_with_rng(&mut rng, difficulty, 2, b"ctx");
+            let bytes = bincode::serialize(&challenge).unwrap();
+            let decoded: Challenge = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes(), challenge.to_bytes());
+        }
+    }
+
+    #[test]
+    fn caller_provided_rng() {
+        let mut rng = rand::rngs::mock::StepRng::new(1, 1);
+        let challenge =
+            create_challenge_with_rng(&mut rng, Difficulty::LeadingZeroBits(4), 2, b"ctx");
+        let proofs = proofs_by_hand(&challenge, b"ctx");
+
+        assert_ne!(challenge.fragments[0], challenge.fragments[1]);
+        assert!(verify_solution(&challenge, &Solution::from_proofs(proofs)));
+    }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn every_algorithm() {
+        for algorithm in HashAlgorithm::ALL {
+            let challenge =
+                create_challenge(Difficulty::LeadingZeroBits(8), 2).with_algorithm(algorithm);
+            let solution =
+                solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default())
+                    .unwrap();
+            assert!(verify_solution(&challenge, &solution));
+
+            // Proofs only hold for the algorithm they were computed with
+            let others = HashAlgorithm::ALL.into_iter().filter(|a| *a != algorithm);
+            let rejected = others
+                .filter(|&a| !verify_solution(&challenge.clone().with_algorithm(a), &solution))
+                .count();
+            assert!(rejected >= 3);
+        }
+    }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn memory_hard() {
+        let algorithm = HashAlgorithm::Argon2id {
+            memory_kib: 256,
+            iterations: 2,
+        };
+        let challenge =
+            create_challenge(Difficulty::LeadingZeroBits(3), 2).with_algorithm(algorithm);
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+
+        assert!(verify_solution(&challenge, &solution));
+    }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn cuckoo_cycle() {
+        let puzzle = Puzzle::CuckooCycle {
+            edge_bits: 12,
+            cycle_len: 8,
+        };
+        let challenge = create_challenge_with_context(Difficulty::LeadingZeroBits(2), 2, b"ctx")
+            .with_puzzle(puzzle)
+            .unwrap();
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+
+        assert_eq!(challenge.expected_attempts(), 64.0);
+        assert!(verify_solution(&challenge, &solution));
+        assert!(!verify_solution_with_context(
+            &challenge, &solution, b"other"
+        ));
+        assert!(!verify_solution(
+            &challenge.clone().with_puzzle(Puzzle::HashTarget).unwrap(),
+            &solution
+        ));
+
+        let mut tampered = solution.clone();
+        tampered.witnesses[0][0] ^= 1;
+        assert!(!verify_solution(&challenge, &tampered));
+        tampered.witnesses[0].clear();
+        assert!(!verify_solution(&challenge, &tampered));
+
+        // Parameters without any cycle to find
+        for (edge_bits, cycle_len) in [(0, 8), (12, 3), (12, 2), (32, 8), (2, 8)] {
+            let puzzle = Puzzle::CuckooCycle {
+                edge_bits,
+                cycle_len,
+            };
+            assert_eq!(
+                challenge.clone().with_puzzle(puzzle).err(),
+                Some(InvalidPuzzle(puzzle))
+            );
+        }
+    }
+
+    #[cfg(feature = "solve")]
+    #[test]
+    fn time_lock() {
+        let challenge = create_challenge_with_context(Difficulty::LeadingZeroBits(64), 1, b"ctx")
+            .with_puzzle(Puzzle::TimeLock { squarings: 5000 })
+            .unwrap();
+        let solution =
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).unwrap();
+
+        assert_eq!(challenge.expected_attempts(), 10000.0);
+        assert!(verify_solution(&challenge, &solution));
+        assert!(!verify_solution_with_context(
+            &challenge, &solution, b"other"
+        ));
+        assert!(!verify_solution(
+            &challenge
+                .clone()
+                .with_puzzle(Puzzle::TimeLock { squarings: 4999 })
+                .unwrap(),
+            &solution
+        ));
+
+        // However many fragments a difficulty policy asked for
+        let (difficulty, fragments) =
+            Difficulty::for_solve_time(1e3, Duration::from_secs(3600 * 24));
+        assert!(fragments > 1);
+        let challenge = create_challenge(difficulty, fragments)
+            .with_puzzle(Puzzle::TimeLock { squarings: 5000 })
+            .unwrap();
+        assert_eq!(challenge.fragments.len(), 1);
+        assert_eq!(challenge.expected_attempts(), 10000.0);
+    }
+
+    #[test]
+    fn verify_errors() {
+        let mut rng = rand::rngs::mock::StepRng::new(1, 1);
+        let challenge = create_challenge_with_rng(&mut rng, Difficulty::LeadingZeroBits(4), 2, &[]);
+        let other = create_challenge_with_rng(&mut rng, Difficulty::LeadingZeroBits(4), 2, &[]);
+        let solve = |challenge: &Challenge| Solution::from_proofs(proofs_by_hand(challenge, &[]));
+        let solution = solve(&challenge);
+        assert_eq!(try_verify_solution(&challenge, &solution), Ok(()));
+
+        assert_eq!(
+            try_verify_solution(&challenge, &solve(&other)),
+            Err(VerifyError::DifferentChallenge(0))
+        );
+
+        let mut missing = solution.clone();
+        missing.proofs.remove(0);
+        assert_eq!(
+            try_verify_solution(&challenge, &missing),
+            Err(VerifyError::MissingFragment(0))
+        );
+
+        let mut wrong = solution.clone();
+        wrong.proofs[1].1 += 1;
+        while puzzle::check(&challenge, wrong.proofs[1].0, wrong.proofs[1].1, &[], &[]) {
+            wrong.proofs[1].1 += 1;
+        }
+        assert_eq!(
+            try_verify_solution(&challenge, &wrong),
+            Err(VerifyError::InvalidNonce(1))
+        );
+
+        let mut malformed = solution;
+        malformed.witnesses.push(alloc::vec![1]);
+        assert_eq!(
+            try_verify_solution(&challenge, &malformed),
+            Err(VerifyError::Malformed)
+        );
+    }
+
+    #[test]
+    fn strict() {
+        let mut rng = rand::rngs::mock::StepRng::new(1, 1);
+        let challenge = create_challenge_with_rng(&mut rng, Difficulty::LeadingZeroBits(4), 3, &[]);
+        let proofs = proofs_by_hand(&challenge, &[]);
+        let solution = Solution::from_proofs(proofs.clone());
+        assert_eq!(verify_solution_strict(&challenge, &solution), Ok(()));
+
+        let strict = |proofs: &[([u8; 16], u128, Vec<u8>)]| {
+            let solution = Solution::from_proofs(proofs.to_vec());
+            (
+                try_verify_solution(&challenge, &solution),
+                verify_solution_strict(&challenge, &solution),
+            )
+        };
+
+        let reordered = [proofs[1].clone(), proofs[0].clone(), proofs[2].clone()];
+        assert_eq!(
+            strict(&reordered),
+            (Ok(()), Err(VerifyError::OutOfOrder(0)))
+        );
+
+        let duplicated = [proofs[0].clone(), proofs[0].clone(), proofs[2].clone()];
+        assert_eq!(
+            strict(&duplicated),
+            (
+                Err(VerifyError::MissingFragment(1)),
+                Err(VerifyError::DuplicateProof(1))
+            )
+        );
+
+        let extra = [&proofs[..], &proofs[..1]].concat();
+        assert_eq!(strict(&extra), (Ok(()), Err(VerifyError::TooManyProofs(4))));
+        assert_eq!(
+            strict(&proofs[..2]),
+            (
+                Err(VerifyError::MissingFragment(2)),
+                Err(VerifyError::MissingFragment(2))
+            )
+        );
+    }
 }
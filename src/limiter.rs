@@ -0,0 +1,309 @@
+//! Rate limiting that only asks for work once a client goes over its quota.
+//!
+//! Requests are counted per key (an IP address, account or API key) over a
+//! sliding window. Within the free quota requests go straight through. Beyond
+//! it, every request needs a challenge solved first, which gets harder the
+//! further over the quota the client is.
+
+use crate::policy::Conditions;
+use crate::{ChallengeIssuer, DifficultyPolicy, IssuedChallenge, RedeemError, Solution};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Where a [`RateLimiter`] keeps its counters. Both operations map onto single
+/// Redis commands, so servers can share their counts through Redis or anything
+/// speaking its protocol.
+pub trait StateStore: Send + Sync {
+    /// Adds one to the counter under `key` and returns the new count. A new
+    /// counter expires after `ttl`, like `INCR` followed by `EXPIRE key ttl NX`.
+    fn increment(&self, key: &[u8], ttl: Duration) -> u64;
+
+    /// The counter under `key`, zero if there is none or it expired, like `GET`
+    fn get(&self, key: &[u8]) -> u64;
+}
+
+/// Counters in this process's memory
+#[derive(Default)]
+pub struct MemoryStore {
+    counters: Mutex<HashMap<Vec<u8>, (u64, Instant)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets every expired counter. Expired counters read as zero anyway,
+    /// this only frees their memory.
+    pub fn purge_expired(&self) {
+        let now = Instant::now();
+        self.counters
+            .lock()
+            .unwrap()
+            .retain(|_, (_, expires)| *expires > now);
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn increment(&self, key: &[u8], ttl: Duration) -> u64 {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap();
+        let (count, expires) = counters.entry(key.to_vec()).or_insert((0, now + ttl));
+        if *expires <= now {
+            (*count, *expires) = (0, now + ttl);
+        }
+
+        *count += 1;
+        *count
+    }
+
+    fn get(&self, key: &[u8]) -> u64 {
+        match self.counters.lock().unwrap().get(key) {
+            Some(&(count, expires)) if expires > Instant::now() => count,
+            _ => 0,
+        }
+    }
+}
+
+// So that several limiters can share one store
+impl<S: StateStore + ?Sized> StateStore for Arc<S> {
+    fn increment(&self, key: &[u8], ttl: Duration) -> u64 {
+        (**self).increment(key, ttl)
+    }
+
+    fn get(&self, key: &[u8]) -> u64 {
+        (**self).get(key)
+    }
+}
+
+/// What [`RateLimiter::admit`] decided about a request
+pub enum Admission {
+    /// Within the free quota, so the request can go ahead
+    Free,
+    /// Over the quota. The request may only go ahead once the client has
+    /// solved this challenge and [redeemed](RateLimiter::redeem) it.
+    Challenge(IssuedChallenge),
+}
+
+/// Lets every key make a number of requests per window for free, and asks for
+/// a proof of work for every request beyond that
+pub struct RateLimiter<S = MemoryStore> {
+    store: S,
+    quota: u64,
+    window: Duration,
+    policy: Box<dyn DifficultyPolicy>,
+    issuer: ChallengeIssuer,
+}
+
+impl<S: StateStore> RateLimiter<S> {
+    /// Allows `quota` free requests per `window` for every key. Beyond that,
+    /// `policy` picks how hard challenges are from the [`Conditions`] during
+    /// the window: how far the key is over its quota, how many of its
+    /// solutions failed and the request rate across all keys.
+    ///
+    /// With [`ExponentialUnderAttack`](crate::policy::ExponentialUnderAttack)
+    /// the work doubles for every quota's worth of requests over it, with
+    /// [`LinearBackoff`](crate::policy::LinearBackoff) it grows by a step.
+    pub fn new(
+        store: S,
+        quota: u64,
+        window: Duration,
+        policy: impl DifficultyPolicy + 'static,
+    ) -> Self {
+        Self {
+            store,
+            quota,
+            window,
+            policy: Box::new(policy),
+            issuer: ChallengeIssuer::new(window),
+        }
+    }
+
+    /// Issues challenges through `issuer`, e.g. for another hash algorithm or
+    /// time to live. Challenges last a window by default.
+    pub fn with_issuer(mut self, issuer: ChallengeIssuer) -> Self {
+        self.issuer = issuer;
+        self
+    }
+
+    /// Counts a request from `key` and decides whether it needs a proof of
+    /// work. Challenges can only be redeemed by the same key.
+    pub fn admit(&self, key: &[u8]) -> Admission {
+        self.admit_under_load(key, 0.0)
+    }
+
+    /// Like [`admit`](Self::admit), with the server's `load` from 0 for idle
+    /// to 1 for saturated, for policies that take it into account
+    pub fn admit_under_load(&self, key: &[u8], load: f64) -> Admission {
+        let everyone = self.count(b"all-requests", b"", true);
+        let requests = self.count(b"requests", key, true);
+        let quota = self.quota as f64;
+        if requests <= quota {
+            return Admission::Free;
+        }
+
+        let conditions = Conditions {
+            request_rate: everyone / self.window.as_secs_f64(),
+            load,
+            failures: self.count(b"failures", key, false) as u32,
+            overage: (requests - quota) / quota.max(1.0),
+        };
+        Admission::Challenge(
+            self.issuer
+                .issue_with_policy(self.policy.as_ref(), &conditions, key),
+        )
+    }
+
+    /// Checks the solution to a challenge [`admit`](Self::admit) handed to
+    /// `key`. Failures make the key's next challenges harder, depending on the
+    /// policy.
+    pub fn redeem(&self, key: &[u8], id: u128, solution: &Solution) -> Result<(), RedeemError> {
+        let result = self.issuer.redeem_with_context(id, solution, key);
+        if result.is_err() {
+            self.count(b"failures", key, true);
+        }
+        result
+    }
+
+    // The number of events of `kind` from `key` during the last window, first
+    // counting a new one if `add`. The previous fixed window counts for as much
+    // of it as still lies within the sliding one.
+    fn count(&self, kind: &[u8], key: &[u8], add: bool) -> f64 {
+        let window = self.window.as_millis().max(1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let (index, into) = (now / window, (now % window) as f64 / window as f64);
+
+        let counter = |index: u128| {
+            [
+                b"effort:",
+                kind,
+                b":",
+                key,
+                b":",
+                index.to_string().as_bytes(),
+            ]
+            .concat()
+        };
+        let current = if add {
+            self.store.increment(&counter(index), 2 * self.window)
+        } else {
+            self.store.get(&counter(index))
+        };
+        let previous = match index.checked_sub(1) {
+            Some(index) => self.store.get(&counter(index)),
+            None => 0,
+        };
+
+        previous as f64 * (1.0 - into) + current as f64
+    }
+}
+
+#[cfg(all(test, feature = "solve"))]
+mod tests {
+    use super::*;
+    use crate::policy::{ExponentialUnderAttack, LinearBackoff};
+    use crate::{solve_challenge_blocking, NoProgress, SolveOptions, VerifyError};
+
+    const QUOTA: u64 = 3;
+    const WINDOW: Duration = Duration::from_secs(600);
+
+    fn limiter<S: StateStore>(store: S) -> RateLimiter<S> {
+        let policy = ExponentialUnderAttack {
+            hashes_per_second: 1e6,
+            solve_time: Duration::from_millis(1),
+            normal_rate: QUOTA as f64 / WINDOW.as_secs_f64(),
+            max: Duration::from_secs(1),
+        };
+        RateLimiter::new(store, QUOTA, WINDOW, policy)
+    }
+
+    // Expected attempts of the challenge, or zero for a free request
+    fn work(admission: Admission) -> f64 {
+        match admission {
+            Admission::Free => 0.0,
+            Admission::Challenge(issued) => issued.challenge.expected_attempts(),
+        }
+    }
+
+    #[test]
+    fn escalates_over_quota() {
+        let limiter = limiter(MemoryStore::new());
+
+        let work: Vec<f64> = (0..9).map(|_| work(limiter.admit(b"alice"))).collect();
+        assert_eq!(work[..3], [0.0; 3]);
+        assert!(work[3] > 0.0);
+        assert!(work.windows(2).all(|w| w[0] <= w[1]), "{work:?}");
+        assert!(work[8] >= 3.0 * work[3], "{work:?}");
+
+        // Everyone has a quota of their own
+        assert!(matches!(limiter.admit(b"bob"), Admission::Free));
+    }
+
+    #[test]
+    fn scales_with_overage() {
+        let policy = LinearBackoff {
+            hashes_per_second: 1e6,
+            solve_time: Duration::from_millis(1),
+            step: Duration::from_millis(1),
+            max: Duration::from_secs(1),
+        };
+        let limiter = RateLimiter::new(MemoryStore::new(), QUOTA, WINDOW, policy);
+
+        let work: Vec<f64> = (0..9).map(|_| work(limiter.admit(b"alice"))).collect();
+        assert_eq!(work[..3], [0.0; 3]);
+        assert!(work.windows(2).all(|w| w[0] <= w[1]), "{work:?}");
+        // A third of a quota over it, then two quotas
+        assert!(work[8] >= 2.0 * work[3], "{work:?}");
+    }
+
+    #[test]
+    fn redeems() {
+        let limiter = limiter(MemoryStore::new());
+        for _ in 0..QUOTA {
+            limiter.admit(b"alice");
+        }
+
+        let Admission::Challenge(issued) = limiter.admit(b"alice") else {
+            panic!("over the quota without a challenge");
+        };
+        let solution =
+            solve_challenge_blocking(&issued.challenge, &NoProgress, &SolveOptions::default())
+                .unwrap();
+        let before = work(limiter.admit(b"alice"));
+
+        // Only the key the challenge was issued to can redeem it
+        assert!(matches!(
+            limiter.redeem(b"mallory", issued.id, &solution),
+            Err(RedeemError::Invalid(VerifyError::InvalidNonce(_)))
+        ));
+        assert_eq!(limiter.redeem(b"alice", issued.id, &solution), Ok(()));
+        assert_eq!(
+            limiter.redeem(b"alice", issued.id, &solution),
+            Err(RedeemError::AlreadyUsed)
+        );
+
+        // Failing doubles the work
+        assert!(work(limiter.admit(b"alice")) >= 2.0 * before);
+    }
+
+    #[test]
+    fn shares_store() {
+        let store = Arc::new(MemoryStore::new());
+        let (a, b) = (limiter(store.clone()), limiter(store.clone()));
+
+        assert!(matches!(a.admit(b"alice"), Admission::Free));
+        assert!(matches!(b.admit(b"alice"), Admission::Free));
+        assert!(matches!(a.admit(b"alice"), Admission::Free));
+        assert!(matches!(b.admit(b"alice"), Admission::Challenge(_)));
+
+        store.increment(b"gone", Duration::ZERO);
+        assert_eq!(store.get(b"gone"), 0);
+        assert_eq!(store.increment(b"gone", Duration::from_secs(60)), 1);
+        store.purge_expired();
+        assert_eq!(store.get(b"gone"), 1);
+    }
+}
@@ -0,0 +1,184 @@
+//! Picking how hard challenges are from what the server is going through,
+//! instead of a difficulty chosen once and for all.
+//!
+//! The built-in policies all start from how long a challenge should take on a
+//! reference device, and turn that into a difficulty with
+//! [`Difficulty::for_solve_time`].
+
+use crate::Difficulty;
+use std::time::Duration;
+
+/// What a [`DifficultyPolicy`] gets to know about the request a challenge is
+/// issued for. Measuring these is up to the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Conditions {
+    /// Challenges requested per second lately, across all clients
+    pub request_rate: f64,
+    /// How busy the server is, from 0 for idle to 1 for saturated
+    pub load: f64,
+    /// Invalid or abandoned solutions lately from the client asking
+    pub failures: u32,
+    /// How far the client asking is over its quota, in multiples of the quota,
+    /// e.g. 1 for twice as many requests as it is allowed. Zero within it.
+    pub overage: f64,
+}
+
+/// Chooses the difficulty and fragment count of every challenge
+pub trait DifficultyPolicy: Send + Sync {
+    fn choose(&self, conditions: &Conditions) -> (Difficulty, usize);
+}
+
+/// The same challenge whatever happens
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Constant {
+    pub difficulty: Difficulty,
+    pub fragments: usize,
+}
+
+impl Constant {
+    /// Challenges that take `solve_time` at `hashes_per_second`
+    pub fn for_solve_time(hashes_per_second: f64, solve_time: Duration) -> Self {
+        let (difficulty, fragments) = Difficulty::for_solve_time(hashes_per_second, solve_time);
+        Self {
+            difficulty,
+            fragments,
+        }
+    }
+}
+
+impl DifficultyPolicy for Constant {
+    fn choose(&self, _: &Conditions) -> (Difficulty, usize) {
+        (self.difficulty, self.fragments)
+    }
+}
+
+/// Adds a fixed amount of work for every failure of the client and every
+/// quota's worth of requests it is over, so that whoever keeps sending garbage
+/// or hammering the server waits longer and longer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearBackoff {
+    /// Speed of the reference device, e.g. as measured by `calibrate` on it
+    pub hashes_per_second: f64,
+    /// Time to solve for a client without failures
+    pub solve_time: Duration,
+    /// Added to the time for every failure and quota's worth of overage
+    pub step: Duration,
+    /// The longest a challenge may take
+    pub max: Duration,
+}
+
+impl DifficultyPolicy for LinearBackoff {
+    fn choose(&self, conditions: &Conditions) -> (Difficulty, usize) {
+        let steps = conditions.failures as f64 + conditions.overage.max(0.0);
+        let time = Duration::try_from_secs_f64(self.step.as_secs_f64() * steps)
+            .ok()
+            .and_then(|backoff| self.solve_time.checked_add(backoff))
+            .unwrap_or(Duration::MAX);
+        Difficulty::for_solve_time(self.hashes_per_second, time.min(self.max))
+    }
+}
+
+/// Leaves honest clients alone while traffic is normal, then doubles the work
+/// for every `normal_rate` of extra requests per second, for a fully loaded
+/// server, for every failure of the client and for every quota's worth of
+/// requests it is over
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExponentialUnderAttack {
+    /// Speed of the reference device, e.g. as measured by `calibrate` on it
+    pub hashes_per_second: f64,
+    /// Time to solve under normal conditions
+    pub solve_time: Duration,
+    /// Requests per second on an ordinary day
+    pub normal_rate: f64,
+    /// The longest a challenge may take
+    pub max: Duration,
+}
+
+impl DifficultyPolicy for ExponentialUnderAttack {
+    fn choose(&self, conditions: &Conditions) -> (Difficulty, usize) {
+        let surge = (conditions.request_rate / self.normal_rate - 1.0).max(0.0);
+        let doublings = surge
+            + conditions.load.clamp(0.0, 1.0)
+            + conditions.failures as f64
+            + conditions.overage.max(0.0);
+        let time = Duration::try_from_secs_f64(self.solve_time.as_secs_f64() * doublings.exp2())
+            .unwrap_or(Duration::MAX);
+        Difficulty::for_solve_time(self.hashes_per_second, time.min(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f64 = 1_000_000.0;
+
+    fn taking(secs: u64) -> (Difficulty, usize) {
+        Difficulty::for_solve_time(RATE, Duration::from_secs(secs))
+    }
+
+    #[test]
+    fn constant() {
+        let policy = Constant::for_solve_time(RATE, Duration::from_secs(2));
+        let attack = Conditions {
+            request_rate: 1e6,
+            load: 1.0,
+            failures: 100,
+            overage: 10.0,
+        };
+        assert_eq!(policy.choose(&Conditions::default()), taking(2));
+        assert_eq!(policy.choose(&attack), taking(2));
+    }
+
+    #[test]
+    fn linear_backoff() {
+        let policy = LinearBackoff {
+            hashes_per_second: RATE,
+            solve_time: Duration::from_secs(1),
+            step: Duration::from_secs(2),
+            max: Duration::from_secs(30),
+        };
+        let failing = |failures| Conditions {
+            failures,
+            ..Default::default()
+        };
+
+        assert_eq!(policy.choose(&failing(0)), taking(1));
+        assert_eq!(policy.choose(&failing(3)), taking(7));
+        assert_eq!(policy.choose(&failing(u32::MAX)), taking(30));
+
+        let over = |overage| Conditions {
+            overage,
+            ..Default::default()
+        };
+        assert_eq!(policy.choose(&over(1.5)), taking(4));
+        assert_eq!(policy.choose(&over(1e9)), taking(30));
+    }
+
+    #[test]
+    fn exponential_under_attack() {
+        let policy = ExponentialUnderAttack {
+            hashes_per_second: RATE,
+            solve_time: Duration::from_secs(1),
+            normal_rate: 100.0,
+            max: Duration::from_secs(60),
+        };
+        let with = |request_rate, load, failures, overage| {
+            policy.choose(&Conditions {
+                request_rate,
+                load,
+                failures,
+                overage,
+            })
+        };
+
+        assert_eq!(with(50.0, 0.0, 0, 0.0), taking(1));
+        assert_eq!(with(100.0, 0.0, 0, 0.0), taking(1));
+        assert_eq!(with(300.0, 0.0, 0, 0.0), taking(4));
+        assert_eq!(with(300.0, 1.0, 1, 0.0), taking(16));
+        assert_eq!(with(300.0, 1.0, 1, 2.0), taking(60));
+        assert_eq!(with(0.0, 0.0, 0, 2.0), taking(4));
+        assert_eq!(with(1e9, 0.0, 0, 0.0), taking(60));
+        assert_eq!(with(0.0, 0.0, 1000, 0.0), taking(60));
+    }
+}
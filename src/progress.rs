@@ -0,0 +1,153 @@
+use core::time::Duration;
+#[cfg(feature = "tokio")]
+use tokio::sync::{broadcast, mpsc, watch};
+
+/// A snapshot of how far along a solve is
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    pub fragments_done: usize,
+    pub fragments_total: usize,
+    /// Hashes tried so far, across all fragments
+    pub hashes: u64,
+    /// Hashes per second since solving started
+    pub hash_rate: f64,
+    /// Expected time until every fragment is solved, if the hash rate is known
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    #[cfg(feature = "solve")]
+    pub(crate) fn new(
+        fragments_done: usize,
+        fragments_total: usize,
+        hashes: u64,
+        elapsed: Duration,
+        expected_attempts: f64,
+    ) -> Self {
+        let hash_rate = if elapsed.is_zero() {
+            0.0
+        } else {
+            hashes as f64 / elapsed.as_secs_f64()
+        };
+
+        // Finding a nonce is memoryless, so every unsolved fragment still needs
+        // the full expected number of attempts no matter how long it's been
+        // worked on
+        let remaining = (fragments_total - fragments_done) as f64 * expected_attempts;
+        let eta = if remaining == 0.0 {
+            Some(Duration::ZERO)
+        } else if hash_rate > 0.0 {
+            Duration::try_from_secs_f64(remaining / hash_rate).ok()
+        } else {
+            None
+        };
+
+        Self {
+            fragments_done,
+            fragments_total,
+            hashes,
+            hash_rate,
+            eta,
+        }
+    }
+
+    /// Fraction of fragments solved, between 0 and 1
+    pub fn fraction(&self) -> f64 {
+        if self.fragments_total == 0 {
+            1.0
+        } else {
+            self.fragments_done as f64 / self.fragments_total as f64
+        }
+    }
+}
+
+/// Somewhere to send [`Progress`] while solving. Progress is best effort, so
+/// sinks drop whatever they can't deliver instead of failing the solve.
+pub trait ProgressSink {
+    fn report(&self, progress: Progress);
+}
+
+/// Discards all progress
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn report(&self, _progress: Progress) {}
+}
+
+/// Calls the wrapped closure with every progress update
+#[derive(Clone, Copy, Debug)]
+pub struct Callback<F>(pub F);
+
+impl<F: Fn(Progress)> ProgressSink for Callback<F> {
+    fn report(&self, progress: Progress) {
+        (self.0)(progress)
+    }
+}
+
+#[cfg(feature = "tokio")]
+// Fails when nobody is subscribed, which is fine
+impl ProgressSink for broadcast::Sender<Progress> {
+    fn report(&self, progress: Progress) {
+        let _ = self.send(progress);
+    }
+}
+
+#[cfg(feature = "tokio")]
+// Drops updates while the channel is full rather than waiting for the receiver
+impl ProgressSink for mpsc::Sender<Progress> {
+    fn report(&self, progress: Progress) {
+        let _ = self.try_send(progress);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ProgressSink for mpsc::UnboundedSender<Progress> {
+    fn report(&self, progress: Progress) {
+        let _ = self.send(progress);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl ProgressSink for watch::Sender<Progress> {
+    fn report(&self, progress: Progress) {
+        self.send_replace(progress);
+    }
+}
+
+#[cfg(all(test, feature = "solve"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_remaining_time() {
+        let progress = Progress::new(1, 4, 3000, Duration::from_secs(3), 2000.0);
+
+        assert_eq!(progress.hash_rate, 1000.0);
+        assert_eq!(progress.eta, Some(Duration::from_secs(6)));
+        assert_eq!(progress.fraction(), 0.25);
+
+        let progress = Progress::new(0, 4, 0, Duration::ZERO, 2000.0);
+        assert_eq!(progress.eta, None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn closed_channels_are_ignored() {
+        let progress = Progress::new(0, 1, 0, Duration::ZERO, 1.0);
+
+        let (tx, rx) = broadcast::channel(1);
+        drop(rx);
+        tx.report(progress);
+
+        let (tx, rx) = mpsc::channel(1);
+        tx.report(progress);
+        tx.report(progress);
+        drop(rx);
+        tx.report(progress);
+
+        let (tx, rx) = watch::channel(progress);
+        drop(rx);
+        tx.report(progress);
+    }
+}
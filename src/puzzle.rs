@@ -0,0 +1,185 @@
+use crate::{cuckoo, timelock, Challenge, PowHasher, WireError};
+use alloc::vec::Vec;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+/// The work each fragment of a challenge asks for. Every kind is keyed by the
+/// fragment, a nonce and the context. All but [`TimeLock`](Self::TimeLock) are
+/// made harder by the challenge's [`Difficulty`](crate::Difficulty).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Puzzle {
+    /// Find a nonce whose hash meets the difficulty. Verifying costs as much as
+    /// a single attempt.
+    #[default]
+    HashTarget,
+    /// Find a nonce whose graph of 2^`edge_bits` edges contains a cycle of
+    /// `cycle_len` edges, and whose hash over that cycle meets the difficulty.
+    /// Solving needs 2^(`edge_bits` + 3) bytes of memory and roughly
+    /// `cycle_len` graphs per fragment, while verifying costs a couple of hashes
+    /// and `cycle_len` SipHashes no matter how large the graph is.
+    ///
+    /// `cycle_len` must be even, at least 4 and at most 2^`edge_bits`, and
+    /// `edge_bits` between 1 and 31.
+    CuckooCycle { edge_bits: u8, cycle_len: u8 },
+    /// Square a number derived from the fragment `squarings` times modulo
+    /// RSA-2048, then prove having done so. The squarings can only happen one
+    /// after another, so a fragment takes the same wall-clock time however many
+    /// cores the client has. Verifying takes a few milliseconds.
+    ///
+    /// Fragments would be solved in parallel, so
+    /// [`with_puzzle`](crate::Challenge::with_puzzle) keeps only the first
+    /// one. The difficulty is ignored.
+    TimeLock { squarings: u32 },
+}
+
+/// Puzzle parameters no nonce can ever solve, so that solving would never end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPuzzle(pub Puzzle);
+
+impl fmt::Display for InvalidPuzzle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsolvable puzzle {:?}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidPuzzle {}
+
+impl Puzzle {
+    /// Fails for parameters without any solution
+    pub fn validate(&self) -> Result<(), InvalidPuzzle> {
+        match *self {
+            Puzzle::CuckooCycle {
+                edge_bits,
+                cycle_len,
+            } if !cuckoo::valid(edge_bits, cycle_len) => Err(InvalidPuzzle(*self)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mean number of attempts per fragment before the difficulty even comes
+    /// into it. Only an estimate for Cuckoo Cycle. A time lock counts its
+    /// squarings, including those for the proof. Infinite for an invalid
+    /// puzzle.
+    pub fn attempts_per_success(&self) -> f64 {
+        if self.validate().is_err() {
+            return f64::INFINITY;
+        }
+
+        match self {
+            Puzzle::HashTarget => 1.0,
+            Puzzle::CuckooCycle { cycle_len, .. } => *cycle_len as f64,
+            Puzzle::TimeLock { squarings } => 2.0 * *squarings as f64,
+        }
+    }
+
+    // Canonical encoding, used wherever a puzzle gets authenticated or sent
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        match self {
+            Puzzle::HashTarget => alloc::vec![0],
+            Puzzle::CuckooCycle {
+                edge_bits,
+                cycle_len,
+            } => alloc::vec![1, edge_bits, cycle_len],
+            Puzzle::TimeLock { squarings } => [&[2][..], &squarings.to_le_bytes()].concat(),
+        }
+    }
+
+    // Reverses `to_bytes`, returning whatever follows the puzzle
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), WireError> {
+        match bytes {
+            [0, rest @ ..] => Ok((Puzzle::HashTarget, rest)),
+            [1, edge_bits, cycle_len, rest @ ..] => {
+                let puzzle = Puzzle::CuckooCycle {
+                    edge_bits: *edge_bits,
+                    cycle_len: *cycle_len,
+                };
+                puzzle.validate().map_err(|_| WireError::InvalidPuzzle)?;
+                Ok((puzzle, rest))
+            }
+            [2, rest @ ..] => {
+                let (squarings, rest) =
+                    rest.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+                let squarings = u32::from_le_bytes(*squarings);
+                Ok((Puzzle::TimeLock { squarings }, rest))
+            }
+            [0..=2, ..] | [] => Err(WireError::Truncated),
+            [kind, ..] => Err(WireError::UnknownPuzzle(*kind)),
+        }
+    }
+}
+
+/// Tries `nonce` on `fragment`, returning the witness that goes with it in the
+/// solution if it works. Long attempts report their progress to `work`, which
+/// returns false to cut them short.
+#[cfg(any(feature = "solve", test))]
+pub(crate) fn attempt(
+    challenge: &Challenge,
+    fragment: [u8; 16],
+    nonce: u128,
+    context: &[u8],
+    work: &dyn Fn(u64) -> bool,
+) -> Option<Vec<u8>> {
+    let mut seed = [0; 64];
+    let len = challenge.algorithm.hash(
+        &[&fragment[..], &nonce.to_le_bytes(), context].concat(),
+        &mut seed,
+    );
+
+    match challenge.puzzle {
+        Puzzle::HashTarget => challenge.difficulty.meets(&seed[..len]).then(Vec::new),
+        Puzzle::CuckooCycle {
+            edge_bits,
+            cycle_len,
+        } => {
+            let edges = cuckoo::find_cycle(&cuckoo::keys(&seed), edge_bits, cycle_len)?;
+            let witness: Vec<u8> = edges.iter().flat_map(|e| e.to_le_bytes()).collect();
+            cycle_meets(challenge, &seed[..len], &witness).then_some(witness)
+        }
+        Puzzle::TimeLock { squarings } => timelock::evaluate(&seed[..len], squarings, work),
+    }
+}
+
+/// Checks that `nonce` and `witness` solve `fragment`
+pub(crate) fn check(
+    challenge: &Challenge,
+    fragment: [u8; 16],
+    nonce: u128,
+    witness: &[u8],
+    context: &[u8],
+) -> bool {
+    let mut seed = [0; 64];
+    let len = challenge.algorithm.hash(
+        &[&fragment[..], &nonce.to_le_bytes(), context].concat(),
+        &mut seed,
+    );
+
+    match challenge.puzzle {
+        Puzzle::HashTarget => witness.is_empty() && challenge.difficulty.meets(&seed[..len]),
+        Puzzle::CuckooCycle {
+            edge_bits,
+            cycle_len,
+        } => {
+            if witness.len() != 4 * cycle_len as usize {
+                return false;
+            }
+
+            let edges: Vec<u32> = witness
+                .chunks_exact(4)
+                .map(|e| u32::from_le_bytes(e.try_into().unwrap()))
+                .collect();
+            cycle_meets(challenge, &seed[..len], witness)
+                && cuckoo::verify_cycle(&cuckoo::keys(&seed), edge_bits, cycle_len, &edges)
+        }
+        Puzzle::TimeLock { squarings } => timelock::verify(&seed[..len], squarings, witness),
+    }
+}
+
+// The difficulty applies to the hash over the graph's seed and its cycle
+fn cycle_meets(challenge: &Challenge, seed: &[u8], witness: &[u8]) -> bool {
+    let mut hash = [0; 64];
+    let len = challenge
+        .algorithm
+        .hash(&[seed, witness].concat(), &mut hash);
+    challenge.difficulty.meets(&hash[..len])
+}
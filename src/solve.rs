@@ -0,0 +1,735 @@
+use crate::lanes::Blake2bLanes;
+use crate::{
+    puzzle, Challenge, Difficulty, HashAlgorithm, Progress, ProgressSink, Puzzle, Solution,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// A solved fragment: the fragment, its nonce and the puzzle's witness
+type Proof = ([u8; 16], u128, Vec<u8>);
+
+/// Where the hashing for [`solve_challenge_with`] runs. Either way it stays off
+/// the async worker threads, so solving doesn't starve the rest of the runtime.
+///
+/// [`solve_challenge_blocking`] always uses dedicated threads, one per available
+/// CPU unless `Threads` says otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolverBackend {
+    /// One task per available CPU on Tokio's blocking thread pool, shared by
+    /// all fragments like `Threads`
+    #[cfg(feature = "tokio")]
+    SpawnBlocking,
+    /// A pool of this many dedicated OS threads, shared by all fragments. When
+    /// threads outnumber the unsolved fragments, they split nonces between them.
+    Threads(usize),
+}
+
+impl Default for SolverBackend {
+    #[cfg(feature = "tokio")]
+    fn default() -> Self {
+        SolverBackend::SpawnBlocking
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn default() -> Self {
+        SolverBackend::Threads(available_threads())
+    }
+}
+
+/// Stops every solve it was handed to. Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SolveOptions {
+    pub backend: SolverBackend,
+    pub cancel: CancelToken,
+    /// Give up once this passes
+    pub deadline: Option<Instant>,
+    /// Give up after trying this many hashes across all fragments
+    pub max_hashes: Option<u64>,
+    /// How often to report [`Progress`] besides whenever a fragment is solved.
+    /// Zero reports only when a fragment is solved.
+    pub progress_interval: Duration,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            backend: SolverBackend::default(),
+            cancel: CancelToken::default(),
+            deadline: None,
+            max_hashes: None,
+            progress_interval: Duration::from_millis(250),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolveError {
+    /// The [`CancelToken`] was cancelled before every fragment was solved
+    Cancelled,
+    /// The deadline passed. Holds the proofs for the fragments solved so far.
+    DeadlineExceeded(Solution),
+    /// The hash budget ran out. Holds the proofs for the fragments solved so far.
+    HashLimitReached(Solution),
+    /// No nonce can solve the challenge's fragments, e.g. because its puzzle
+    /// parameters are invalid
+    Unsolvable,
+}
+
+impl fmt::Display for SolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolveError::Cancelled => write!(f, "solving was cancelled"),
+            SolveError::DeadlineExceeded(_) => write!(f, "solving deadline exceeded"),
+            SolveError::HashLimitReached(_) => write!(f, "solving hash limit reached"),
+            SolveError::Unsolvable => write!(f, "challenge can't be solved"),
+        }
+    }
+}
+
+impl std::error::Error for SolveError {}
+
+// Fragment workers report their work here and poll it to find out whether they
+// should give up. `dropped` is set when the solving future goes away, so that
+// nobody keeps burning CPU for it.
+#[derive(Clone)]
+struct Stop {
+    dropped: Arc<AtomicBool>,
+    cancel: CancelToken,
+    deadline: Option<Instant>,
+    max_hashes: Option<u64>,
+    hashes: Arc<AtomicU64>,
+}
+
+impl Stop {
+    fn new(options: &SolveOptions, dropped: Arc<AtomicBool>) -> Self {
+        Self {
+            dropped,
+            cancel: options.cancel.clone(),
+            deadline: options.deadline,
+            max_hashes: options.max_hashes,
+            hashes: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.dropped.load(Ordering::Relaxed)
+            || self.cancel.is_cancelled()
+            || self.deadline_passed()
+            || self.out_of_hashes()
+    }
+
+    fn deadline_passed(&self) -> bool {
+        self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    fn out_of_hashes(&self) -> bool {
+        self.max_hashes
+            .is_some_and(|max| self.hashes.load(Ordering::Relaxed) >= max)
+    }
+
+    fn record(&self, hashes: u64) {
+        self.hashes.fetch_add(hashes, Ordering::Relaxed);
+    }
+
+    fn progress(&self, challenge: &Challenge, done: usize, started: Instant) -> Progress {
+        Progress::new(
+            done,
+            challenge.fragments.len(),
+            self.hashes.load(Ordering::Relaxed),
+            started.elapsed(),
+            challenge.attempts_per_fragment(),
+        )
+    }
+
+    fn finish(
+        &self,
+        challenge: &Challenge,
+        mut proofs: Vec<Proof>,
+    ) -> Result<Solution, SolveError> {
+        // Proofs arrive in whatever order the workers finish, but strict
+        // verification wants them in challenge order
+        let order: HashMap<_, _> = challenge
+            .fragments
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f, i))
+            .collect();
+        proofs.sort_by_key(|p| order[&p.0]);
+
+        let solved = proofs.len();
+        let solution = Solution::from_proofs(proofs);
+        if solved == challenge.fragments.len() {
+            return Ok(solution);
+        }
+
+        // Why not every fragment got solved
+        if self.cancel.is_cancelled() {
+            Err(SolveError::Cancelled)
+        } else if self.deadline_passed() {
+            Err(SolveError::DeadlineExceeded(solution))
+        } else {
+            Err(SolveError::HashLimitReached(solution))
+        }
+    }
+}
+
+struct StopOnDrop(Arc<AtomicBool>);
+
+impl Drop for StopOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Solves every fragment of `challenge`. Dropping the returned future stops all
+/// fragment workers.
+///
+/// # Panics
+///
+/// If the challenge can't be solved, see [`SolveError::Unsolvable`]
+#[cfg(feature = "tokio")]
+pub async fn solve_challenge(challenge: &Challenge, progress: &impl ProgressSink) -> Solution {
+    solve_challenge_with(challenge, progress, &SolveOptions::default())
+        .await
+        .expect("solve can only stop early with options or an unsolvable challenge")
+}
+
+#[cfg(feature = "tokio")]
+pub async fn solve_challenge_with(
+    challenge: &Challenge,
+    progress: &impl ProgressSink,
+    options: &SolveOptions,
+) -> Result<Solution, SolveError> {
+    let stop = Stop::new(options, Arc::new(AtomicBool::new(false)));
+    solve_until_dropped(challenge, progress, options, stop).await
+}
+
+// Tells the workers to stop through `stop` once the future is dropped. Split
+// out so that tests can keep watching `stop` after that.
+#[cfg(feature = "tokio")]
+async fn solve_until_dropped(
+    challenge: &Challenge,
+    progress: &impl ProgressSink,
+    options: &SolveOptions,
+    stop: Stop,
+) -> Result<Solution, SolveError> {
+    use tokio::sync::mpsc;
+    use tokio::time::MissedTickBehavior;
+
+    let _guard = StopOnDrop(stop.dropped.clone());
+    if unsolvable(challenge) {
+        return Err(SolveError::Unsolvable);
+    }
+
+    let started = Instant::now();
+    let (tx, mut solutions) = mpsc::unbounded_channel();
+    let send = move |solution| tx.send(solution).is_ok();
+    match options.backend {
+        SolverBackend::SpawnBlocking => {
+            spawn_workers(challenge, available_threads(), &stop, send, |worker| {
+                tokio::task::spawn_blocking(worker);
+            });
+        }
+        SolverBackend::Threads(threads) => {
+            spawn_workers(challenge, threads, &stop, send, |worker| {
+                thread::spawn(worker);
+            });
+        }
+    }
+
+    // Tokio refuses a zero period, but then the ticker goes unused anyway
+    let ticking = !options.progress_interval.is_zero();
+    let mut ticker = tokio::time::interval(options.progress_interval.max(Duration::from_millis(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut proofs = vec![];
+    loop {
+        tokio::select! {
+            solution = solutions.recv() => match solution {
+                Some(solution) => proofs.push(solution),
+                None => break,
+            },
+            _ = ticker.tick(), if ticking => {}
+        }
+
+        // Notify of progress
+        progress.report(stop.progress(challenge, proofs.len(), started));
+    }
+
+    stop.finish(challenge, proofs)
+}
+
+/// Solves `challenge` on dedicated threads, blocking until done. Needs no async
+/// runtime.
+pub fn solve_challenge_blocking(
+    challenge: &Challenge,
+    progress: &impl ProgressSink,
+    options: &SolveOptions,
+) -> Result<Solution, SolveError> {
+    if unsolvable(challenge) {
+        return Err(SolveError::Unsolvable);
+    }
+
+    // Only infallible without the `tokio` feature
+    #[allow(clippy::infallible_destructuring_match)]
+    let threads = match options.backend {
+        SolverBackend::Threads(threads) => threads,
+        #[cfg(feature = "tokio")]
+        SolverBackend::SpawnBlocking => available_threads(),
+    };
+
+    // Nothing can drop this solve halfway through, but the threads still need
+    // telling should this thread panic
+    let dropped = Arc::new(AtomicBool::new(false));
+    let _guard = StopOnDrop(dropped.clone());
+    let stop = Stop::new(options, dropped);
+
+    let started = Instant::now();
+    let (tx, solutions) = mpsc::channel();
+    let send = move |solution| tx.send(solution).is_ok();
+    spawn_workers(challenge, threads, &stop, send, |worker| {
+        thread::spawn(worker);
+    });
+
+    let mut proofs = vec![];
+    loop {
+        let received = if options.progress_interval.is_zero() {
+            solutions.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            solutions.recv_timeout(options.progress_interval)
+        };
+        match received {
+            Ok(solution) => proofs.push(solution),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        // Notify of progress
+        progress.report(stop.progress(challenge, proofs.len(), started));
+    }
+
+    stop.finish(challenge, proofs)
+}
+
+/// Measures how many hashes per second this machine manages with `algorithm`,
+/// hashing on every CPU for about `duration`. The rate can be passed to
+/// [`Difficulty::for_solve_time`] or [`Challenge::estimated_solve_time`].
+pub fn calibrate(algorithm: HashAlgorithm, duration: Duration) -> f64 {
+    // A fragment nobody can solve, so that every thread hashes until the end
+    let challenge = Challenge {
+        difficulty: Difficulty::LeadingZeroBits(u16::MAX),
+        fragments: vec![[0; 16]],
+        context: Vec::new(),
+        algorithm,
+        puzzle: Puzzle::HashTarget,
+    };
+    let options = SolveOptions {
+        deadline: Some(Instant::now() + duration),
+        ..Default::default()
+    };
+
+    let dropped = Arc::new(AtomicBool::new(false));
+    let _guard = StopOnDrop(dropped.clone());
+    let stop = Stop::new(&options, dropped);
+
+    let started = Instant::now();
+    let (tx, solutions) = mpsc::channel();
+    spawn_workers(
+        &challenge,
+        available_threads(),
+        &stop,
+        move |solution| tx.send(solution).is_ok(),
+        |worker| {
+            thread::spawn(worker);
+        },
+    );
+
+    // Closes once every worker is past the deadline
+    solutions.iter().for_each(drop);
+    stop.hashes.load(Ordering::Relaxed) as f64 / started.elapsed().as_secs_f64()
+}
+
+// Challenges that could come from anywhere, e.g. deserialized, get this far
+// without their parameters being checked. Workers would search forever.
+fn unsolvable(challenge: &Challenge) -> bool {
+    !challenge.fragments.is_empty() && challenge.attempts_per_fragment().is_infinite()
+}
+
+fn available_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// One fragment's nonce search, shared by every worker on it. Workers claim
+// batches of nonces in turn, so none is tried twice.
+struct Job {
+    fragment: [u8; 16],
+    next_batch: AtomicU64,
+    workers: AtomicUsize,
+    solved: AtomicBool,
+}
+
+// Starts `workers` workers through `spawn`, handing each solution to `send`.
+// Once every worker is done, `send` is dropped.
+fn spawn_workers<F, S>(challenge: &Challenge, workers: usize, stop: &Stop, send: F, spawn: S)
+where
+    F: Fn(Proof) -> bool + Clone + Send + 'static,
+    S: Fn(Box<dyn FnOnce() + Send>),
+{
+    let shared = Arc::new(challenge.clone());
+    let jobs: Arc<[Job]> = challenge
+        .fragments
+        .iter()
+        .map(|&fragment| Job {
+            fragment,
+            next_batch: AtomicU64::new(0),
+            workers: AtomicUsize::new(0),
+            solved: AtomicBool::new(false),
+        })
+        .collect();
+
+    let workers = if jobs.is_empty() { 0 } else { workers.max(1) };
+    for first in 0..workers {
+        let (challenge, jobs, send, stop) =
+            (shared.clone(), jobs.clone(), send.clone(), stop.clone());
+        spawn(Box::new(move || {
+            work(&challenge, &jobs, first, &stop, &send)
+        }));
+    }
+}
+
+// Works on the first unsolved fragment from `first` on, wrapping around, so
+// workers spread over the fragments and gang up on whichever remain
+fn work(
+    challenge: &Challenge,
+    jobs: &[Job],
+    first: usize,
+    stop: &Stop,
+    send: &impl Fn(Proof) -> bool,
+) {
+    // A time lock is as fast with one worker as with many
+    let lanes = match challenge.puzzle {
+        Puzzle::TimeLock { .. } => 1,
+        _ => usize::MAX,
+    };
+
+    loop {
+        let open = (0..jobs.len())
+            .map(|k| &jobs[(first + k) % jobs.len()])
+            .find(|j| {
+                !j.solved.load(Ordering::Relaxed) && j.workers.load(Ordering::Relaxed) < lanes
+            });
+        let Some(job) = open else {
+            return;
+        };
+
+        job.workers.fetch_add(1, Ordering::Relaxed);
+        let found = solve_fragment(challenge, job, stop);
+        job.workers.fetch_sub(1, Ordering::Relaxed);
+
+        let Some(proof) = found else {
+            if stop.is_set() {
+                return;
+            }
+            continue;
+        };
+
+        // Only the first worker to solve a fragment reports it
+        if !job.solved.swap(true, Ordering::Relaxed) && !send(proof) {
+            return;
+        }
+    }
+}
+
+fn solve_fragment(challenge: &Challenge, job: &Job, stop: &Stop) -> Option<Proof> {
+    // Limits are only checked between batches, to keep that off the hot path.
+    // Attempts that take milliseconds each are checked one by one.
+    let batch: u128 = match (challenge.puzzle, challenge.algorithm) {
+        (Puzzle::HashTarget, HashAlgorithm::Argon2id { .. }) => 1,
+        (Puzzle::HashTarget, _) => 1024,
+        // A time lock takes whichever nonce comes first, it just takes a while
+        (Puzzle::CuckooCycle { .. } | Puzzle::TimeLock { .. }, _) => 1,
+    };
+    // Plain BLAKE2b hashes several nonces per call
+    let lanes = matches!(
+        (challenge.puzzle, challenge.algorithm),
+        (Puzzle::HashTarget, HashAlgorithm::Blake2b512)
+    )
+    .then(|| Blake2bLanes::new(job.fragment, &challenge.context));
+    let gave_up = || stop.is_set() || job.solved.load(Ordering::Relaxed);
+    let work = |done| {
+        stop.record(done);
+        !gave_up()
+    };
+
+    loop {
+        if gave_up() {
+            return None;
+        }
+
+        let start = job.next_batch.fetch_add(1, Ordering::Relaxed) as u128 * batch;
+        let mut nonces = start..start + batch;
+        let found = match &lanes {
+            Some(lanes) => lanes
+                .find(nonces, &challenge.difficulty)
+                .map(|nonce| (nonce, Vec::new())),
+            None => nonces.find_map(|nonce| {
+                puzzle::attempt(challenge, job.fragment, nonce, &challenge.context, &work)
+                    .map(|witness| (nonce, witness))
+            }),
+        };
+        if let Some((nonce, witness)) = found {
+            stop.record((nonce - start + 1) as u64);
+            return Some((job.fragment, nonce, witness));
+        }
+
+        stop.record(batch as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_challenge, verify_solution_strict, Callback, NoProgress};
+
+    #[test]
+    fn blocking() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(8), 8);
+        let reports = AtomicU64::new(0);
+        let progress = Callback(|_| {
+            reports.fetch_add(1, Ordering::Relaxed);
+        });
+
+        let solution =
+            solve_challenge_blocking(&challenge, &progress, &SolveOptions::default()).unwrap();
+
+        assert_eq!(verify_solution_strict(&challenge, &solution), Ok(()));
+        assert!(reports.load(Ordering::Relaxed) >= 8);
+
+        let options = SolveOptions {
+            max_hashes: Some(10_000),
+            ..Default::default()
+        };
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(128), 4);
+        assert_eq!(
+            solve_challenge_blocking(&challenge, &NoProgress, &options).err(),
+            Some(SolveError::HashLimitReached(Solution::default()))
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn backends() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(8), 8);
+        let (tx, rx) = tokio::sync::watch::channel(Progress::new(0, 8, 0, Duration::ZERO, 1.0));
+
+        for backend in [SolverBackend::SpawnBlocking, SolverBackend::Threads(3)] {
+            let options = SolveOptions {
+                backend,
+                ..Default::default()
+            };
+            let solution = solve_challenge_with(&challenge, &tx, &options)
+                .await
+                .unwrap();
+
+            assert_eq!(solution.proofs.len(), 8);
+            assert_eq!(verify_solution_strict(&challenge, &solution), Ok(()));
+            assert_eq!(rx.borrow().fragments_done, 8);
+        }
+    }
+
+    #[test]
+    fn zero_progress_interval() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(8), 8);
+        let reports = AtomicU64::new(0);
+        let progress = Callback(|_| {
+            reports.fetch_add(1, Ordering::Relaxed);
+        });
+        let options = SolveOptions {
+            progress_interval: Duration::ZERO,
+            ..Default::default()
+        };
+
+        // Once per fragment and no more
+        solve_challenge_blocking(&challenge, &progress, &options).unwrap();
+        assert_eq!(reports.swap(0, Ordering::Relaxed), 8);
+
+        #[cfg(feature = "tokio")]
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            solve_challenge_with(&challenge, &progress, &options)
+                .await
+                .unwrap();
+            assert_eq!(reports.load(Ordering::Relaxed), 8);
+        });
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn cancels() {
+        // Practically unsolvable
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(128), 4);
+
+        for backend in [SolverBackend::SpawnBlocking, SolverBackend::Threads(2)] {
+            let options = SolveOptions {
+                backend,
+                ..Default::default()
+            };
+
+            let cancel = options.cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                cancel.cancel();
+            });
+
+            let result = solve_challenge_with(&challenge, &NoProgress, &options).await;
+            assert_eq!(result.err(), Some(SolveError::Cancelled));
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn stops_when_dropped() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(128), 4);
+
+        for backend in [SolverBackend::SpawnBlocking, SolverBackend::Threads(2)] {
+            let options = SolveOptions {
+                backend,
+                ..Default::default()
+            };
+            let stop = Stop::new(&options, Arc::new(AtomicBool::new(false)));
+            let hashes = stop.hashes.clone();
+
+            let solve = solve_until_dropped(&challenge, &NoProgress, &options, stop);
+            let timeout = tokio::time::timeout(Duration::from_millis(50), solve).await;
+            assert!(timeout.is_err());
+
+            // Workers finish the batch they are on, then no more hashes
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let after = hashes.load(Ordering::Relaxed);
+            assert!(after > 0);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert_eq!(hashes.load(Ordering::Relaxed), after, "{backend:?}");
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn gives_up() {
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(128), 4);
+
+        let options = SolveOptions {
+            deadline: Some(Instant::now() + Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let result = solve_challenge_with(&challenge, &NoProgress, &options).await;
+        assert_eq!(
+            result.err(),
+            Some(SolveError::DeadlineExceeded(Solution::default()))
+        );
+
+        let options = SolveOptions {
+            max_hashes: Some(10_000),
+            ..Default::default()
+        };
+        let result = solve_challenge_with(&challenge, &NoProgress, &options).await;
+        assert_eq!(
+            result.err(),
+            Some(SolveError::HashLimitReached(Solution::default()))
+        );
+    }
+
+    #[test]
+    fn refuses_unsolvable() {
+        let mut challenge = create_challenge(Difficulty::LeadingZeroBits(8), 2);
+        // As if deserialized, which doesn't check the parameters
+        challenge.puzzle = Puzzle::CuckooCycle {
+            edge_bits: 2,
+            cycle_len: 8,
+        };
+        assert_eq!(
+            solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).err(),
+            Some(SolveError::Unsolvable)
+        );
+
+        // Nor can any difficulty that needs a longer digest than the algorithm's
+        let too_hard = [
+            Difficulty::Legacy(u32::MAX),
+            Difficulty::LeadingZeroBits(300),
+            Difficulty::Target(Difficulty::target_for_bits(300)),
+        ];
+        for difficulty in too_hard {
+            let challenge = create_challenge(difficulty, 2).with_algorithm(HashAlgorithm::Sha256);
+            assert_eq!(challenge.expected_attempts(), f64::INFINITY);
+            assert_eq!(challenge.estimated_solve_time(1e6), Duration::MAX);
+            assert_eq!(
+                solve_challenge_blocking(&challenge, &NoProgress, &SolveOptions::default()).err(),
+                Some(SolveError::Unsolvable)
+            );
+        }
+    }
+
+    #[test]
+    fn calibrates() {
+        let started = Instant::now();
+        let rate = calibrate(HashAlgorithm::Blake2b512, Duration::from_millis(100));
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert!(rate > 1000.0, "{rate}");
+
+        // A challenge tuned to this rate solves in roughly the time asked for
+        let (difficulty, fragments) = Difficulty::for_solve_time(rate, Duration::from_secs(1));
+        let challenge = create_challenge(difficulty, fragments);
+        let estimate = challenge.estimated_solve_time(rate);
+        assert!(estimate > Duration::from_millis(900) && estimate < Duration::from_millis(1100));
+    }
+
+    #[test]
+    fn shares_fragments() {
+        // One fragment, hard enough to keep several threads busy
+        let challenge = create_challenge(Difficulty::LeadingZeroBits(16), 1);
+        let options = SolveOptions {
+            backend: SolverBackend::Threads(4),
+            ..Default::default()
+        };
+        let solution = solve_challenge_blocking(&challenge, &NoProgress, &options).unwrap();
+        assert_eq!(verify_solution_strict(&challenge, &solution), Ok(()));
+
+        let stop = Stop::new(&options, Arc::new(AtomicBool::new(false)));
+        let (tx, rx) = mpsc::channel();
+        let spawned = AtomicUsize::new(0);
+        spawn_workers(
+            &challenge,
+            4,
+            &stop,
+            move |p| tx.send(p).is_ok(),
+            |worker| {
+                spawned.fetch_add(1, Ordering::Relaxed);
+                thread::spawn(worker);
+            },
+        );
+
+        // Exactly one proof, however many workers found one
+        assert_eq!(rx.iter().count(), 1);
+        assert_eq!(spawned.load(Ordering::Relaxed), 4);
+    }
+}
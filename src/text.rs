@@ -0,0 +1,197 @@
+use crate::{Challenge, Solution, WireError};
+use alloc::vec::Vec;
+use blake2::{Blake2s256, Digest};
+use core::fmt::{self, Write};
+use core::str::FromStr;
+
+// base64url without padding, safe in URLs, headers and QR codes alike
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const CHECKSUM_LEN: usize = 4;
+
+/// Why a string couldn't be parsed into a [`Challenge`] or [`Solution`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// A character outside the base64url alphabet, at this byte offset
+    InvalidCharacter { position: usize, found: char },
+    /// No base64 string has this many characters
+    InvalidLength(usize),
+    /// Too short to even hold the checksum
+    TooShort,
+    /// The string was mistyped or cut off somewhere
+    BadChecksum,
+    /// The decoded bytes aren't a valid value
+    Wire(WireError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::InvalidCharacter { position, found } => {
+                write!(f, "invalid character {found:?} at position {position}")
+            }
+            ParseError::InvalidLength(len) => write!(f, "invalid length {len}"),
+            ParseError::TooShort => write!(f, "too short to hold a checksum"),
+            ParseError::BadChecksum => write!(f, "checksum mismatch"),
+            ParseError::Wire(e) => write!(f, "malformed contents: {e}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Wire(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<WireError> for ParseError {
+    fn from(e: WireError) -> Self {
+        ParseError::Wire(e)
+    }
+}
+
+/// The [wire format](Challenge::to_bytes) followed by a four-byte checksum, in
+/// unpadded base64url
+impl fmt::Display for Challenge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(&self.to_bytes(), f)
+    }
+}
+
+impl FromStr for Challenge {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Challenge::from_bytes(&decode(s)?)?)
+    }
+}
+
+/// The [wire format](Solution::to_bytes) followed by a four-byte checksum, in
+/// unpadded base64url
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        encode(&self.to_bytes(), f)
+    }
+}
+
+impl FromStr for Solution {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Solution::from_bytes(&decode(s)?)?)
+    }
+}
+
+fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    Blake2s256::digest(bytes)[..CHECKSUM_LEN]
+        .try_into()
+        .unwrap()
+}
+
+fn encode(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let data = [bytes, &checksum(bytes)].concat();
+
+    // Every three bytes become four characters, a shorter tail one more
+    // character than it has bytes
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            f.write_char(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char)?;
+        }
+    }
+    Ok(())
+}
+
+fn decode(s: &str) -> Result<Vec<u8>, ParseError> {
+    if s.len() % 4 == 1 {
+        return Err(ParseError::InvalidLength(s.len()));
+    }
+
+    let mut data = Vec::with_capacity(s.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for (position, found) in s.char_indices() {
+        let value = ALPHABET
+            .iter()
+            .position(|&c| c as char == found)
+            .ok_or(ParseError::InvalidCharacter { position, found })?;
+
+        acc = acc << 6 | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    let split = data
+        .len()
+        .checked_sub(CHECKSUM_LEN)
+        .ok_or(ParseError::TooShort)?;
+    let (bytes, sum) = data.split_at(split);
+    if sum != checksum(bytes) {
+        return Err(ParseError::BadChecksum);
+    }
+
+    data.truncate(split);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_challenge_with_rng, Difficulty};
+    use alloc::string::ToString;
+
+    #[test]
+    fn round_trip() {
+        let mut rng = rand::rngs::mock::StepRng::new(1, 1);
+        let challenge =
+            create_challenge_with_rng(&mut rng, Difficulty::LeadingZeroBits(8), 3, b"/login");
+        let text = challenge.to_string();
+
+        assert!(text
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+        let parsed: Challenge = text.parse().unwrap();
+        assert_eq!(parsed.to_bytes(), challenge.to_bytes());
+
+        let solution = Solution::from_proofs(alloc::vec![([7; 16], 42, alloc::vec![1, 2])]);
+        assert_eq!(solution.to_string().parse(), Ok(solution));
+    }
+
+    #[test]
+    fn descriptive_errors() {
+        let text = Solution::default().to_string();
+
+        assert_eq!(
+            "ab=c".parse::<Solution>(),
+            Err(ParseError::InvalidCharacter {
+                position: 2,
+                found: '='
+            })
+        );
+        assert_eq!(
+            "abcde".parse::<Solution>(),
+            Err(ParseError::InvalidLength(5))
+        );
+        assert_eq!("abc".parse::<Solution>(), Err(ParseError::TooShort));
+
+        let mut typo = text.clone().into_bytes();
+        typo[3] = if typo[3] == b'A' { b'B' } else { b'A' };
+        assert_eq!(
+            core::str::from_utf8(&typo).unwrap().parse::<Solution>(),
+            Err(ParseError::BadChecksum)
+        );
+        assert_eq!(
+            text.parse::<Challenge>().err(),
+            Some(ParseError::Wire(WireError::UnexpectedKind(1)))
+        );
+    }
+}
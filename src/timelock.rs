@@ -0,0 +1,172 @@
+//! Wesolowski's verifiable delay function: raise a seed to 2^squarings modulo
+//! an RSA modulus nobody can factor. Without the factors, the only known way
+//! is one squaring after another, so extra cores don't help. The proof lets
+//! the result be checked with two exponentiations of about 128 bits each.
+
+use alloc::vec::Vec;
+use blake2::{Blake2b512, Digest};
+use num_bigint::BigUint;
+
+// RSA-2048 from the RSA Factoring Challenge, whose factors nobody knows
+const MODULUS: &[u8] = b"\
+    25195908475657893494027183240048398571429282126204032027777137836043662020707595556264018525\
+    88078440691829064124951508218929855914917618450280848912007284499268739280728777673597141834\
+    72702618963750149718246911650776133798590957000973304597488084284017974291006424586918171951\
+    18746121515172654632282216869987549182422433637259085141865462043576798423387184774447920739\
+    93423658482382428119816381501067481045166037730605620161967625613384414360383390441495263443\
+    21901146575444541784240209246165157233507787077498171257724679629263863563732899121548314381\
+    67899885040445364023527381951378636564391212010397122822120720357";
+
+/// Length of the result and of the proof in the witness
+const ELEMENT_LEN: usize = 256;
+
+// Small primes for trial division, and bases for Miller-Rabin
+const PRIMES: [u32; 16] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53];
+
+/// Computes the result and proof for `seed`, reporting every 1024 squarings
+/// to `work`, which returns false to give up
+#[cfg(any(feature = "solve", test))]
+pub(crate) fn evaluate(seed: &[u8], squarings: u32, work: &dyn Fn(u64) -> bool) -> Option<Vec<u8>> {
+    const STEP: u32 = 1024;
+    let n = modulus();
+    let x = BigUint::from_bytes_be(seed) % &n;
+
+    let mut y = x.clone();
+    for i in 0..squarings {
+        if i % STEP == 0 && i > 0 && !work(STEP as u64) {
+            return None;
+        }
+        y = &y * &y % &n;
+    }
+
+    // The proof is x^(2^squarings / l), built one bit of the quotient at a time
+    let l = hash_to_prime(&x, &y);
+    let (mut pi, mut r) = (BigUint::from(1u32), BigUint::from(1u32));
+    for i in 0..squarings {
+        if i % STEP == 0 && i > 0 && !work(STEP as u64) {
+            return None;
+        }
+        r <<= 1;
+        pi = &pi * &pi % &n;
+        if r >= l {
+            r -= &l;
+            pi = pi * &x % &n;
+        }
+    }
+
+    Some([to_element(&y), to_element(&pi)].concat())
+}
+
+/// Checks that `witness` holds the result for `seed` and a valid proof of it
+pub(crate) fn verify(seed: &[u8], squarings: u32, witness: &[u8]) -> bool {
+    if witness.len() != 2 * ELEMENT_LEN {
+        return false;
+    }
+
+    let n = modulus();
+    let (y, pi) = witness.split_at(ELEMENT_LEN);
+    let (y, pi) = (BigUint::from_bytes_be(y), BigUint::from_bytes_be(pi));
+    if y >= n || pi >= n {
+        return false;
+    }
+
+    // pi^l * x^(2^squarings mod l) = x^(2^squarings) = y
+    let x = BigUint::from_bytes_be(seed) % &n;
+    let l = hash_to_prime(&x, &y);
+    let r = BigUint::from(2u32).modpow(&BigUint::from(squarings), &l);
+    pi.modpow(&l, &n) * x.modpow(&r, &n) % &n == y
+}
+
+fn modulus() -> BigUint {
+    let digits: Vec<u8> = MODULUS.iter().copied().filter(u8::is_ascii_digit).collect();
+    BigUint::parse_bytes(&digits, 10).unwrap()
+}
+
+fn to_element(value: &BigUint) -> [u8; ELEMENT_LEN] {
+    let bytes = value.to_bytes_be();
+    let mut element = [0; ELEMENT_LEN];
+    element[ELEMENT_LEN - bytes.len()..].copy_from_slice(&bytes);
+    element
+}
+
+// A 128-bit prime derived from both ends of the computation, so that the proof
+// can't be prepared before the result is known
+fn hash_to_prime(x: &BigUint, y: &BigUint) -> BigUint {
+    (0u32..)
+        .map(|counter| {
+            let digest = Blake2b512::new()
+                .chain_update(b"effort-timelock")
+                .chain_update(to_element(x))
+                .chain_update(to_element(y))
+                .chain_update(counter.to_le_bytes())
+                .finalize();
+            let candidate = BigUint::from_bytes_be(&digest[..16]);
+            candidate | (BigUint::from(1u32) << 127u32) | BigUint::from(1u32)
+        })
+        .find(is_prime)
+        .unwrap()
+}
+
+fn is_prime(n: &BigUint) -> bool {
+    for p in PRIMES {
+        if n % p == BigUint::from(0u32) {
+            return *n == BigUint::from(p);
+        }
+    }
+
+    let one = BigUint::from(1u32);
+    let n1 = n - 1u32;
+    let s = n1.trailing_zeros().unwrap();
+    let d = &n1 >> s;
+
+    'bases: for a in PRIMES {
+        let mut x = BigUint::from(a).modpow(&d, n);
+        if x == one || x == n1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n1 {
+                continue 'bases;
+            }
+        }
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_and_verifies() {
+        let work = |_| true;
+        let witness = evaluate(b"seed", 3000, &work).unwrap();
+        assert!(verify(b"seed", 3000, &witness));
+        assert!(!verify(b"seed", 2999, &witness));
+        assert!(!verify(b"other", 3000, &witness));
+        assert!(!verify(b"seed", 3000, &witness[1..]));
+
+        // Four squarings of 3 give 3^16
+        let witness = evaluate(&[3], 4, &work).unwrap();
+        assert_eq!(
+            BigUint::from_bytes_be(&witness[..ELEMENT_LEN]),
+            43046721u32.into()
+        );
+
+        let mut tampered = witness.clone();
+        tampered[2 * ELEMENT_LEN - 1] ^= 1;
+        assert!(!verify(&[3], 4, &tampered));
+
+        assert!(evaluate(b"seed", 3000, &|_| false).is_none());
+    }
+
+    #[test]
+    fn primes() {
+        assert!(is_prime(&BigUint::from(u64::MAX - 58)));
+        assert!(!is_prime(&BigUint::from(u64::MAX)));
+        assert!(!is_prime(&(BigUint::from(4294967291u64) * 4294967279u64)));
+    }
+}
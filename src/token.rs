@@ -0,0 +1,314 @@
+use crate::{
+    create_challenge_with_context, verify_solution_strict_with_context, Challenge, Difficulty,
+    HashAlgorithm, InvalidPuzzle, Puzzle, Solution, VerifyError,
+};
+use blake2::digest::consts::U32;
+use blake2::digest::Mac;
+use blake2::Blake2bMac;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Tag = Blake2bMac<U32>;
+
+/// A challenge together with its expiry and an authentication tag. The client
+/// sends it back unchanged along with its [`Solution`], so the verifier doesn't
+/// need to remember anything about the challenges it created.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChallengeToken {
+    pub challenge: Challenge,
+    /// Seconds since the Unix epoch after which the token is no longer accepted
+    pub expires: u64,
+    tag: [u8; 32],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenError {
+    /// The token was not created with this secret, or has been tampered with
+    BadTag,
+    Expired,
+    /// The challenge is bound to a context, but none was given to check the
+    /// solution in
+    MissingContext,
+    /// The solution does not solve the challenge
+    Invalid(VerifyError),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::BadTag => write!(f, "challenge token failed authentication"),
+            TokenError::Expired => write!(f, "challenge token expired"),
+            TokenError::MissingContext => write!(f, "challenge token needs a context"),
+            TokenError::Invalid(e) => write!(f, "invalid solution: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Creates and verifies [`ChallengeToken`]s under a server secret using keyed
+/// BLAKE2b. Every node sharing the secret can verify every token.
+///
+/// Being stateless, this can't tell whether a token was already redeemed. Use
+/// [`ChallengeIssuer`](crate::ChallengeIssuer) where replays matter.
+pub struct ChallengeSigner {
+    key: [u8; 32],
+    ttl: Duration,
+    algorithm: HashAlgorithm,
+    puzzle: Puzzle,
+}
+
+impl ChallengeSigner {
+    pub fn new(key: [u8; 32], ttl: Duration) -> Self {
+        Self {
+            key,
+            ttl,
+            algorithm: HashAlgorithm::default(),
+            puzzle: Puzzle::default(),
+        }
+    }
+
+    /// Creates challenges that must be solved with `algorithm`
+    pub fn with_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.algorithm = algorithm;
+        self
+    }
+
+    /// Creates challenges whose fragments ask for `puzzle` rather than a hash
+    /// below the target, unless it has no solutions
+    pub fn with_puzzle(mut self, puzzle: Puzzle) -> Result<Self, InvalidPuzzle> {
+        puzzle.validate()?;
+        self.puzzle = puzzle;
+        Ok(self)
+    }
+
+    pub fn create_challenge(
+        &self,
+        difficulty: impl Into<Difficulty>,
+        num_fragments: usize,
+    ) -> ChallengeToken {
+        self.create_challenge_with_context(difficulty, num_fragments, &[])
+    }
+
+    /// Creates a token that only verifies for requests with the same `context`,
+    /// see [`create_challenge_with_context`]
+    pub fn create_challenge_with_context(
+        &self,
+        difficulty: impl Into<Difficulty>,
+        num_fragments: usize,
+        context: &[u8],
+    ) -> ChallengeToken {
+        let challenge = create_challenge_with_context(difficulty, num_fragments, context)
+            .with_algorithm(self.algorithm)
+            .with_puzzle(self.puzzle)
+            .expect("validated by with_puzzle");
+        let expires = unix_now() + self.ttl.as_secs();
+        let tag = self.mac(&challenge, expires).finalize().into_bytes().into();
+
+        ChallengeToken {
+            challenge,
+            expires,
+            tag,
+        }
+    }
+
+    /// Verifies `solution` to a token created without a context. Tokens bound
+    /// to one fail with [`TokenError::MissingContext`], as the token's own
+    /// context is whatever the client sent back.
+    pub fn verify_solution(
+        &self,
+        token: &ChallengeToken,
+        solution: &Solution,
+    ) -> Result<(), TokenError> {
+        self.verify_in(token, solution, None)
+    }
+
+    /// Verifies `solution` with `context` taken from the request presenting
+    /// the token, so that a token solved for one client fails for any other
+    pub fn verify_solution_with_context(
+        &self,
+        token: &ChallengeToken,
+        solution: &Solution,
+        context: &[u8],
+    ) -> Result<(), TokenError> {
+        self.verify_in(token, solution, Some(context))
+    }
+
+    fn verify_in(
+        &self,
+        token: &ChallengeToken,
+        solution: &Solution,
+        context: Option<&[u8]>,
+    ) -> Result<(), TokenError> {
+        self.mac(&token.challenge, token.expires)
+            .verify_slice(&token.tag)
+            .map_err(|_| TokenError::BadTag)?;
+
+        if token.expires <= unix_now() {
+            return Err(TokenError::Expired);
+        }
+
+        let context = match context {
+            Some(context) => context,
+            None if token.challenge.context.is_empty() => &[],
+            None => return Err(TokenError::MissingContext),
+        };
+        verify_solution_strict_with_context(&token.challenge, solution, context)
+            .map_err(TokenError::Invalid)?;
+
+        Ok(())
+    }
+
+    fn mac(&self, challenge: &Challenge, expires: u64) -> Tag {
+        let mut mac = <Tag as Mac>::new_from_slice(&self.key).unwrap();
+        mac.update(b"effort-token-v2");
+        mac.update(&expires.to_le_bytes());
+        // The wire encoding prefixes every field of variable length with its
+        // length, so no bytes can move from one field to another
+        mac.update(&challenge.to_bytes());
+        mac
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(all(test, feature = "solve"))]
+mod tests {
+    use super::*;
+    use crate::{solve_challenge_blocking, NoProgress, SolveOptions};
+
+    #[test]
+    fn verifies_on_another_node() {
+        let node_a = ChallengeSigner::new([7; 32], Duration::from_secs(60));
+        let node_b = ChallengeSigner::new([7; 32], Duration::from_secs(60));
+        let token = node_a.create_challenge(1000, 2);
+
+        let solution =
+            solve_challenge_blocking(&token.challenge, &NoProgress, &SolveOptions::default())
+                .unwrap();
+        assert_eq!(node_b.verify_solution(&token, &solution), Ok(()));
+
+        let other = ChallengeSigner::new([8; 32], Duration::from_secs(60));
+        assert_eq!(
+            other.verify_solution(&token, &solution),
+            Err(TokenError::BadTag)
+        );
+
+        let mut tampered = token.clone();
+        tampered.challenge.difficulty = Difficulty::Legacy(0);
+        assert_eq!(
+            node_b.verify_solution(&tampered, &solution),
+            Err(TokenError::BadTag)
+        );
+    }
+
+    #[test]
+    fn verifies_in_signed_context() {
+        let signer = ChallengeSigner::new([7; 32], Duration::from_secs(60));
+        let token =
+            signer.create_challenge_with_context(Difficulty::LeadingZeroBits(16), 1, b"/login");
+        let solution =
+            solve_challenge_blocking(&token.challenge, &NoProgress, &SolveOptions::default())
+                .unwrap();
+
+        assert_eq!(
+            signer.verify_solution_with_context(&token, &solution, b"/login"),
+            Ok(())
+        );
+        assert_eq!(
+            signer.verify_solution_with_context(&token, &solution, b"/other"),
+            Err(TokenError::Invalid(VerifyError::InvalidNonce(0)))
+        );
+    }
+
+    #[test]
+    fn relayed_tokens_need_a_context() {
+        let signer = ChallengeSigner::new([7; 32], Duration::from_secs(60));
+        let token = signer.create_challenge_with_context(
+            Difficulty::LeadingZeroBits(16),
+            1,
+            b"198.51.100.1",
+        );
+        let solution =
+            solve_challenge_blocking(&token.challenge, &NoProgress, &SolveOptions::default())
+                .unwrap();
+
+        // Another client hands in the first one's token and solution
+        assert_eq!(
+            signer.verify_solution(&token, &solution),
+            Err(TokenError::MissingContext)
+        );
+        assert_eq!(
+            signer.verify_solution_with_context(&token, &solution, b"198.51.100.2"),
+            Err(TokenError::Invalid(VerifyError::InvalidNonce(0)))
+        );
+    }
+
+    #[test]
+    fn signs_puzzles() {
+        let puzzle = Puzzle::CuckooCycle {
+            edge_bits: 12,
+            cycle_len: 8,
+        };
+        let signer = ChallengeSigner::new([7; 32], Duration::from_secs(60))
+            .with_puzzle(puzzle)
+            .unwrap();
+        let token = signer.create_challenge(Difficulty::LeadingZeroBits(2), 2);
+        let solution =
+            solve_challenge_blocking(&token.challenge, &NoProgress, &SolveOptions::default())
+                .unwrap();
+
+        assert_eq!(token.challenge.puzzle, puzzle);
+        assert_eq!(signer.verify_solution(&token, &solution), Ok(()));
+
+        let mut tampered = token.clone();
+        tampered.challenge.puzzle = Puzzle::HashTarget;
+        assert_eq!(
+            signer.verify_solution(&tampered, &solution),
+            Err(TokenError::BadTag)
+        );
+    }
+
+    #[test]
+    fn fields_cannot_trade_bytes() {
+        let signer = ChallengeSigner::new([7; 32], Duration::from_secs(60));
+        let argon2id = HashAlgorithm::Argon2id {
+            memory_kib: 64,
+            iterations: 1,
+        };
+
+        // The context ends in what would encode Argon2id, but for the last
+        // byte, which matches the ID of the default algorithm
+        let mut context = b"/login".to_vec();
+        context.extend(&argon2id.to_bytes()[..8]);
+        let token = signer.create_challenge_with_context(1000, 1, &context);
+        assert_eq!(token.challenge.algorithm.to_bytes(), [0]);
+
+        let mut moved = token.clone();
+        moved.challenge.context.truncate(b"/login".len());
+        moved.challenge.algorithm = argon2id;
+        let solution = Solution::default();
+        assert_eq!(
+            signer.verify_solution(&moved, &solution),
+            Err(TokenError::BadTag)
+        );
+    }
+
+    #[test]
+    fn rejects_expired() {
+        let signer = ChallengeSigner::new([7; 32], Duration::ZERO);
+        let token = signer.create_challenge(1000, 0);
+        let solution = Solution::default();
+
+        assert_eq!(
+            signer.verify_solution(&token, &solution),
+            Err(TokenError::Expired)
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{Challenge, Difficulty, HashAlgorithm, Puzzle, Solution};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+const MAGIC: u8 = 0xef;
+const VERSION: u8 = 1;
+const CHALLENGE: u8 = 0;
+const SOLUTION: u8 = 1;
+
+/// Why bytes couldn't be decoded into a [`Challenge`] or [`Solution`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireError {
+    /// The first byte isn't the magic byte, so this isn't an encoded value
+    BadMagic(u8),
+    /// Encoded by a version of the format this crate doesn't know
+    UnsupportedVersion(u8),
+    /// A solution where a challenge was expected, or the other way around
+    UnexpectedKind(u8),
+    UnknownAlgorithm(u8),
+    UnknownPuzzle(u8),
+    /// Puzzle parameters no nonce can ever solve
+    InvalidPuzzle,
+    UnknownDifficulty(u8),
+    /// The bytes end in the middle of the value
+    Truncated,
+    /// This many bytes follow the value
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::BadMagic(byte) => write!(f, "bad magic byte {byte:#04x}"),
+            WireError::UnsupportedVersion(v) => write!(f, "unsupported wire format version {v}"),
+            WireError::UnexpectedKind(kind) => write!(f, "unexpected value kind {kind}"),
+            WireError::UnknownAlgorithm(id) => write!(f, "unknown hash algorithm {id}"),
+            WireError::UnknownPuzzle(kind) => write!(f, "unknown puzzle {kind}"),
+            WireError::InvalidPuzzle => write!(f, "unsolvable puzzle parameters"),
+            WireError::UnknownDifficulty(kind) => write!(f, "unknown difficulty {kind}"),
+            WireError::Truncated => write!(f, "truncated data"),
+            WireError::TrailingBytes(n) => write!(f, "{n} trailing bytes"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WireError {}
+
+impl Challenge {
+    /// Encodes the challenge in version 1 of the wire format. Integers are
+    /// little-endian and lengths are `u32`s.
+    ///
+    /// - the magic byte `0xEF`, the version `1` and the kind `0`
+    /// - the hash algorithm ID, followed by the memory in KiB and the
+    ///   iterations as `u32`s for Argon2id
+    /// - the puzzle: `0` for a hash target; `1`, the edge bits and the cycle
+    ///   length for Cuckoo Cycle; `2` and the squarings as a `u32` for a time
+    ///   lock
+    /// - the difficulty: `0` and a `u32` for legacy, `1` and a `u16` for
+    ///   leading zero bits, `2` and the 64-byte big-endian target
+    /// - the fragment count, then the 16-byte fragments
+    /// - the context length, then the context
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![MAGIC, VERSION, CHALLENGE];
+        bytes.extend(self.algorithm.to_bytes());
+        bytes.extend(self.puzzle.to_bytes());
+        bytes.extend(self.difficulty.to_bytes());
+        bytes.extend((self.fragments.len() as u32).to_le_bytes());
+        bytes.extend(self.fragments.iter().flatten());
+        bytes.extend((self.context.len() as u32).to_le_bytes());
+        bytes.extend(&self.context);
+        bytes
+    }
+
+    /// Decodes a challenge encoded by [`to_bytes`](Self::to_bytes). Fails
+    /// unless `bytes` hold exactly one well-formed challenge.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let rest = read_header(bytes, CHALLENGE)?;
+        let (algorithm, rest) = HashAlgorithm::from_bytes(rest)?;
+        let (puzzle, rest) = Puzzle::from_bytes(rest)?;
+        let (difficulty, rest) = Difficulty::from_bytes(rest)?;
+
+        let (count, rest) = read_u32(rest)?;
+        let len = (count as usize)
+            .checked_mul(16)
+            .ok_or(WireError::Truncated)?;
+        let (fragments, rest) = read_bytes(rest, len)?;
+        let (len, rest) = read_u32(rest)?;
+        let (context, rest) = read_bytes(rest, len as usize)?;
+
+        finish(rest)?;
+        Ok(Challenge {
+            difficulty,
+            fragments: fragments
+                .chunks_exact(16)
+                .map(|f| f.try_into().unwrap())
+                .collect(),
+            context: context.to_vec(),
+            algorithm,
+            puzzle,
+        })
+    }
+}
+
+impl Solution {
+    /// Encodes the solution in version 1 of the wire format, laid out like
+    /// [`Challenge::to_bytes`]:
+    ///
+    /// - the magic byte `0xEF`, the version `1` and the kind `1`
+    /// - the proof count, then for every proof the 16-byte fragment, the nonce
+    ///   as a `u128`, the witness length and the witness
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![MAGIC, VERSION, SOLUTION];
+        bytes.extend((self.proofs.len() as u32).to_le_bytes());
+        for (i, (fragment, nonce)) in self.proofs.iter().enumerate() {
+            let witness = self.witness(i);
+            bytes.extend(fragment);
+            bytes.extend(nonce.to_le_bytes());
+            bytes.extend((witness.len() as u32).to_le_bytes());
+            bytes.extend(witness);
+        }
+        bytes
+    }
+
+    /// Decodes a solution encoded by [`to_bytes`](Self::to_bytes). Fails
+    /// unless `bytes` hold exactly one well-formed solution.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let (count, mut rest) = read_u32(read_header(bytes, SOLUTION)?)?;
+
+        // Every proof takes at least 36 bytes, so a bogus count can't make
+        // this allocate more than the input
+        let mut proofs = Vec::new();
+        for _ in 0..count {
+            let (fragment, tail) = rest.split_first_chunk::<16>().ok_or(WireError::Truncated)?;
+            let (nonce, tail) = tail.split_first_chunk::<16>().ok_or(WireError::Truncated)?;
+            let (len, tail) = read_u32(tail)?;
+            let (witness, tail) = read_bytes(tail, len as usize)?;
+
+            proofs.push((*fragment, u128::from_le_bytes(*nonce), witness.to_vec()));
+            rest = tail;
+        }
+
+        finish(rest)?;
+        Ok(Solution::from_proofs(proofs))
+    }
+}
+
+fn read_header(bytes: &[u8], kind: u8) -> Result<&[u8], WireError> {
+    let (&magic, rest) = bytes.split_first().ok_or(WireError::Truncated)?;
+    if magic != MAGIC {
+        return Err(WireError::BadMagic(magic));
+    }
+
+    let (&version, rest) = rest.split_first().ok_or(WireError::Truncated)?;
+    if version != VERSION {
+        return Err(WireError::UnsupportedVersion(version));
+    }
+
+    let (&found, rest) = rest.split_first().ok_or(WireError::Truncated)?;
+    if found != kind {
+        return Err(WireError::UnexpectedKind(found));
+    }
+
+    Ok(rest)
+}
+
+fn read_u32(bytes: &[u8]) -> Result<(u32, &[u8]), WireError> {
+    let (value, rest) = bytes.split_first_chunk::<4>().ok_or(WireError::Truncated)?;
+    Ok((u32::from_le_bytes(*value), rest))
+}
+
+fn read_bytes(bytes: &[u8], len: usize) -> Result<(&[u8], &[u8]), WireError> {
+    bytes.split_at_checked(len).ok_or(WireError::Truncated)
+}
+
+fn finish(rest: &[u8]) -> Result<(), WireError> {
+    match rest.len() {
+        0 => Ok(()),
+        n => Err(WireError::TrailingBytes(n)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        let digits: Vec<u8> = s.bytes().filter(u8::is_ascii_hexdigit).collect();
+        digits
+            .chunks(2)
+            .map(|d| u8::from_str_radix(core::str::from_utf8(d).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn golden_challenges() {
+        let challenge = Challenge {
+            difficulty: Difficulty::Legacy(1000),
+            fragments: vec![[0x11; 16]],
+            context: b"ctx".to_vec(),
+            algorithm: HashAlgorithm::Blake2b512,
+            puzzle: Puzzle::HashTarget,
+        };
+        let golden = hex("ef 01 00  00  00  00 e8030000
+            01000000 11111111111111111111111111111111
+            03000000 637478");
+        assert_eq!(challenge.to_bytes(), golden);
+        assert_eq!(Challenge::from_bytes(&golden).unwrap().to_bytes(), golden);
+
+        let challenge = Challenge {
+            difficulty: Difficulty::LeadingZeroBits(20),
+            fragments: vec![[0xaa; 16], [0xbb; 16]],
+            context: vec![],
+            algorithm: HashAlgorithm::Argon2id {
+                memory_kib: 256,
+                iterations: 2,
+            },
+            puzzle: Puzzle::TimeLock { squarings: 100_000 },
+        };
+        let golden = hex("ef 01 00  05 00010000 02000000  02 a0860100  01 1400
+            02000000 aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb
+            00000000");
+        assert_eq!(challenge.to_bytes(), golden);
+        assert_eq!(Challenge::from_bytes(&golden).unwrap().to_bytes(), golden);
+    }
+
+    #[test]
+    fn golden_solution() {
+        let solution = Solution {
+            proofs: vec![([0x11; 16], 0x0102)],
+            witnesses: vec![],
+        };
+        let golden = hex("ef 01 01  01000000
+            11111111111111111111111111111111 02010000000000000000000000000000 00000000");
+        assert_eq!(solution.to_bytes(), golden);
+        assert_eq!(Solution::from_bytes(&golden), Ok(solution));
+
+        let solution = Solution {
+            proofs: vec![([0x22; 16], 7)],
+            witnesses: vec![vec![1, 2, 3]],
+        };
+        let golden = hex("ef 01 01  01000000
+            22222222222222222222222222222222 07000000000000000000000000000000 03000000 010203");
+        assert_eq!(solution.to_bytes(), golden);
+        assert_eq!(Solution::from_bytes(&golden), Ok(solution));
+    }
+
+    #[test]
+    fn strict() {
+        let challenge = Challenge {
+            difficulty: Difficulty::Target([0x0f; 64]),
+            fragments: vec![[1; 16], [2; 16]],
+            context: b"ctx".to_vec(),
+            algorithm: HashAlgorithm::Sha256,
+            puzzle: Puzzle::CuckooCycle {
+                edge_bits: 20,
+                cycle_len: 42,
+            },
+        };
+        let bytes = challenge.to_bytes();
+        for len in 0..bytes.len() {
+            assert_eq!(
+                Challenge::from_bytes(&bytes[..len]).err(),
+                Some(WireError::Truncated)
+            );
+        }
+
+        let with = |i: usize, byte: u8| {
+            let mut bytes = bytes.clone();
+            bytes[i] = byte;
+            Challenge::from_bytes(&bytes).err()
+        };
+        assert_eq!(with(0, 0x7b), Some(WireError::BadMagic(0x7b)));
+        assert_eq!(with(1, 2), Some(WireError::UnsupportedVersion(2)));
+        assert_eq!(with(2, 1), Some(WireError::UnexpectedKind(1)));
+        assert_eq!(with(3, 9), Some(WireError::UnknownAlgorithm(9)));
+        assert_eq!(with(4, 9), Some(WireError::UnknownPuzzle(9)));
+        assert_eq!(with(5, 0), Some(WireError::InvalidPuzzle));
+        assert_eq!(with(6, 3), Some(WireError::InvalidPuzzle));
+        assert_eq!(with(7, 9), Some(WireError::UnknownDifficulty(9)));
+
+        let trailing = [&bytes[..], &[0]].concat();
+        assert_eq!(
+            Challenge::from_bytes(&trailing).err(),
+            Some(WireError::TrailingBytes(1))
+        );
+
+        // A huge proof count must not be taken at its word
+        assert_eq!(
+            Solution::from_bytes(&hex("ef 01 01 ffffffff")),
+            Err(WireError::Truncated)
+        );
+        assert_eq!(
+            Solution::from_bytes(&challenge.to_bytes()),
+            Err(WireError::UnexpectedKind(0))
+        );
+    }
+}